@@ -0,0 +1,400 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::error::{Error, Result};
+use crate::output::Overwrite;
+
+/// Name of the project-local configuration file, looked up in the current
+/// directory and its parents.
+pub const PROJECT_CONFIG_NAME: &str = ".extr.toml";
+
+/// Defaults for extracting, merged from the user configuration file, the
+/// closest project-local `.extr.toml` and `EXTR_*` environment variables,
+/// later sources taking precedence. Unset values keep the built-in
+/// defaults.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Directory to extract into
+    pub output_dir: Option<PathBuf>,
+    /// What to do when the output already exists
+    pub overwrite: Option<Overwrite>,
+    /// Put the contents of archives with several top level entries in a
+    /// directory named after the archive
+    pub wrap: Option<bool>,
+    /// Let the tools print their own progress
+    pub verbose: Option<bool>,
+    /// Retry with the next installed tool when one fails
+    pub fallback: Option<bool>,
+    /// Directories trusted to hold tools, on top of the system ones. Only
+    /// read from the user configuration and the environment.
+    pub trusted_paths: Option<Vec<PathBuf>>,
+    /// Commands run after each extraction, `{file}` is replaced by the
+    /// archive and `{out}` by where its contents ended up. Only read from
+    /// the user configuration and the environment.
+    pub post_extract: Option<Vec<String>>,
+    /// Ordered preferred tools per format, e.g. `zip = ["bsdtar", "unzip"]`
+    pub backends: BTreeMap<String, Vec<String>>,
+    /// Additional formats by name. Only read from the user configuration.
+    pub extractors: BTreeMap<String, ExtractorConfig>,
+    /// Where each value was set, keyed like `overwrite` or `backends.zip`
+    #[serde(skip)]
+    pub sources: BTreeMap<String, Source>,
+}
+
+/// A format extr has no built-in support for, handled by the tools named in
+/// `binaries`, e.g.
+///
+/// ```toml
+/// [extractors.lzx]
+/// extensions = ["lzx"]
+/// magic = ["4c 5a 58"]
+/// binaries = ["unlzx"]
+/// args = { unlzx = ["-x", "{file}"] }
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExtractorConfig {
+    /// Lowercase file extensions, without the leading dot
+    pub extensions: Vec<String>,
+    /// Hex encoded bytes identifying files whose name does not give the
+    /// format away, any one of them has to match
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub magic: Vec<String>,
+    /// Where in the file the magic bytes are
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub magic_offset: u64,
+    /// Tools able to extract the format, most preferred first
+    pub binaries: Vec<String>,
+    /// Arguments for each tool in `binaries`. `{file}` is replaced by the
+    /// archive, `{out}` by the output directory, which the tool runs in, and
+    /// `{password}` by `$EXTR_PASSWORD`. Other users on the system can read
+    /// arguments, tools that can take the password from their environment
+    /// or stdin should get it from there instead.
+    pub args: BTreeMap<String, Vec<String>>,
+    /// Type `$EXTR_PASSWORD` into the tool, for tools that prompt for it
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub password_stdin: bool,
+}
+
+fn is_zero(value: &u64) -> bool {
+    *value == 0
+}
+
+/// Where a configuration value came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Default,
+    File(PathBuf),
+    Env(String),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Default => f.write_str("default"),
+            Source::File(path) => write!(f, "{}", path.display()),
+            Source::Env(name) => write!(f, "${name}"),
+        }
+    }
+}
+
+impl Config {
+    /// Reads and merges every configuration source.
+    pub fn load() -> Result<Config> {
+        let mut config = Config::default();
+
+        if let Some(path) = config_path().filter(|path| path.is_file()) {
+            let user = read_file(&path)?;
+            config.merge(user, Source::File(path));
+        }
+
+        if let Some(path) = project_config_path() {
+            let project = read_file(&path)?;
+            // A cloned repository must not get to replace what is outside it
+            for (key, set) in [
+                ("overwrite", project.overwrite.is_some()),
+                ("trusted_paths", project.trusted_paths.is_some()),
+                ("post_extract", project.post_extract.is_some()),
+                ("extractors", !project.extractors.is_empty()),
+            ] {
+                if set {
+                    return Err(Error::Config {
+                        path: Some(path),
+                        message: format!(
+                            "{key} can only be set in the user configuration, not in {PROJECT_CONFIG_NAME}"
+                        ),
+                    });
+                }
+            }
+            let root = path.parent().unwrap_or(Path::new("."));
+            if let Some(output_dir) = &project.output_dir {
+                let escapes = output_dir
+                    .components()
+                    .any(|component| component == Component::ParentDir);
+                if escapes || !output_dir.starts_with(root) {
+                    return Err(Error::Config {
+                        message: format!(
+                            "output_dir has to stay inside {}, the directory of {PROJECT_CONFIG_NAME}",
+                            root.display()
+                        ),
+                        path: Some(path),
+                    });
+                }
+            }
+            config.merge(project, Source::File(path));
+        }
+
+        for (name, value) in env::vars_os() {
+            let Some(name) = name.to_str().filter(|name| name.starts_with("EXTR_")) else {
+                continue;
+            };
+            if let Some(layer) = from_env(name, &value.to_string_lossy())? {
+                config.merge(layer, Source::Env(name.to_string()));
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Overrides the values set in `other`, recording `source` for them.
+    pub fn merge(&mut self, other: Config, source: Source) {
+        fn take<T>(
+            value: &mut Option<T>,
+            other: Option<T>,
+            key: &str,
+            source: &Source,
+            sources: &mut BTreeMap<String, Source>,
+        ) {
+            if other.is_some() {
+                *value = other;
+                sources.insert(key.to_string(), source.clone());
+            }
+        }
+
+        let sources = &mut self.sources;
+        take(
+            &mut self.output_dir,
+            other.output_dir,
+            "output_dir",
+            &source,
+            sources,
+        );
+        take(
+            &mut self.overwrite,
+            other.overwrite,
+            "overwrite",
+            &source,
+            sources,
+        );
+        take(&mut self.wrap, other.wrap, "wrap", &source, sources);
+        take(
+            &mut self.verbose,
+            other.verbose,
+            "verbose",
+            &source,
+            sources,
+        );
+        take(
+            &mut self.fallback,
+            other.fallback,
+            "fallback",
+            &source,
+            sources,
+        );
+        take(
+            &mut self.trusted_paths,
+            other.trusted_paths,
+            "trusted_paths",
+            &source,
+            sources,
+        );
+        take(
+            &mut self.post_extract,
+            other.post_extract,
+            "post_extract",
+            &source,
+            sources,
+        );
+
+        for (format, tools) in other.backends {
+            let format = format.to_lowercase();
+            sources.insert(format!("backends.{format}"), source.clone());
+            self.backends.insert(format, tools);
+        }
+
+        for (name, extractor) in other.extractors {
+            sources.insert(format!("extractors.{name}"), source.clone());
+            self.extractors.insert(name, extractor);
+        }
+    }
+
+    /// Every effective value as a TOML key, value and where it came from,
+    /// built-in defaults included.
+    pub fn entries(&self) -> Vec<(String, String, Source)> {
+        let path = |path: &Path| toml::Value::String(path.to_string_lossy().into_owned());
+        let strings = |values: &[String]| {
+            toml::Value::Array(values.iter().cloned().map(toml::Value::String).collect())
+        };
+
+        let mut entries = vec![
+            (
+                "output_dir",
+                path(self.output_dir.as_deref().unwrap_or(Path::new("."))),
+            ),
+            (
+                "overwrite",
+                toml::Value::String(self.overwrite.unwrap_or_default().to_string()),
+            ),
+            ("wrap", toml::Value::Boolean(self.wrap.unwrap_or(false))),
+            (
+                "verbose",
+                toml::Value::Boolean(self.verbose.unwrap_or(false)),
+            ),
+            (
+                "fallback",
+                toml::Value::Boolean(self.fallback.unwrap_or(false)),
+            ),
+            (
+                "trusted_paths",
+                toml::Value::Array(
+                    self.trusted_paths
+                        .iter()
+                        .flatten()
+                        .map(|trusted| path(trusted))
+                        .collect(),
+                ),
+            ),
+            (
+                "post_extract",
+                strings(self.post_extract.as_deref().unwrap_or_default()),
+            ),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect::<Vec<_>>();
+
+        for (format, tools) in &self.backends {
+            let key = if format.contains('.') {
+                format!("backends.\"{format}\"")
+            } else {
+                format!("backends.{format}")
+            };
+            entries.push((key, strings(tools)));
+        }
+
+        for (name, extractor) in &self.extractors {
+            if let Ok(value) = toml::Value::try_from(extractor) {
+                entries.push((format!("extractors.{name}"), value));
+            }
+        }
+
+        entries
+            .into_iter()
+            .map(|(key, value)| {
+                let lookup = key.replace('"', "");
+                let source = self
+                    .sources
+                    .get(&lookup)
+                    .cloned()
+                    .unwrap_or(Source::Default);
+                (key, value.to_string(), source)
+            })
+            .collect()
+    }
+}
+
+/// Reads one configuration file. Relative paths in it are relative to the
+/// directory of the file, a leading `~` stands for the home directory.
+fn read_file(path: &Path) -> Result<Config> {
+    let content = fs::read_to_string(path).map_err(|error| Error::Config {
+        path: Some(path.to_path_buf()),
+        message: error.to_string(),
+    })?;
+
+    let mut config: Config = toml::from_str(&content).map_err(|error| Error::Config {
+        path: Some(path.to_path_buf()),
+        message: error.message().to_string(),
+    })?;
+
+    let base = path.parent().unwrap_or(Path::new("."));
+    if let Some(output_dir) = &mut config.output_dir {
+        *output_dir = resolve_path(base, output_dir);
+    }
+    for trusted in config.trusted_paths.iter_mut().flatten() {
+        *trusted = resolve_path(base, trusted);
+    }
+    Ok(config)
+}
+
+fn resolve_path(base: &Path, path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), env::var_os("HOME")) {
+        (Ok(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => base.join(path),
+    }
+}
+
+/// The setting of one `EXTR_*` environment variable. Formats in
+/// `EXTR_BACKENDS_*` use `_` for `.`, as in `EXTR_BACKENDS_TAR_GZ=tar`.
+fn from_env(name: &str, value: &str) -> Result<Option<Config>> {
+    let invalid = |message: String| Error::Config {
+        path: None,
+        message: format!("invalid ${name}: {message}"),
+    };
+    let flag = || match value.to_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(Some(true)),
+        "0" | "false" | "no" | "off" => Ok(Some(false)),
+        _ => Err(invalid(format!("expected true or false, got {value}"))),
+    };
+    let list = || {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect::<Vec<_>>()
+    };
+
+    let mut config = Config::default();
+    match name {
+        "EXTR_OUTPUT_DIR" => config.output_dir = Some(PathBuf::from(value)),
+        "EXTR_OVERWRITE" => config.overwrite = Some(value.parse().map_err(invalid)?),
+        "EXTR_WRAP" => config.wrap = flag()?,
+        "EXTR_VERBOSE" => config.verbose = flag()?,
+        "EXTR_FALLBACK" => config.fallback = flag()?,
+        "EXTR_TRUSTED_PATHS" => {
+            config.trusted_paths = Some(env::split_paths(value).collect());
+        }
+        "EXTR_POST_EXTRACT" => config.post_extract = Some(vec![value.to_string()]),
+        name => match name.strip_prefix("EXTR_BACKENDS_") {
+            Some(format) => {
+                let format = format.to_lowercase().replace('_', ".");
+                config.backends.insert(format, list());
+            }
+            None => return Ok(None),
+        },
+    }
+    Ok(Some(config))
+}
+
+pub fn config_path() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+    Some(config_home.join("extr").join("config.toml"))
+}
+
+/// The closest `.extr.toml` in the current directory or one of its parents.
+pub fn project_config_path() -> Option<PathBuf> {
+    let current_dir = env::current_dir().ok()?;
+    current_dir
+        .ancestors()
+        .map(|dir| dir.join(PROJECT_CONFIG_NAME))
+        .find(|path| path.is_file())
+}
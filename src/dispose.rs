@@ -0,0 +1,306 @@
+//! What happens to an archive once everything in it has been extracted:
+//! kept, deleted, moved to the trash or moved to another directory. All
+//! volumes of a multi-volume set go together.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+
+/// What to do with an archive after it was extracted successfully.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Disposal {
+    /// Leave it where it is
+    #[default]
+    Keep,
+    /// Remove it
+    Delete,
+    /// Move it to the trash of the desktop, following the freedesktop.org
+    /// trash specification
+    Trash,
+    /// Move it into this directory, which is created if missing
+    MoveTo(PathBuf),
+}
+
+impl Disposal {
+    /// Deletes or moves `file` and the other volumes of its set, returning
+    /// the files that were acted on.
+    pub fn apply(&self, file: &Path) -> Result<Vec<PathBuf>> {
+        if *self == Disposal::Keep {
+            return Ok(Vec::new());
+        }
+
+        let volumes = volumes(file)?;
+        // Keep the set together, rather than stopping half way through
+        if let Disposal::MoveTo(dir) = self {
+            for volume in &volumes {
+                let target = dir.join(volume.file_name().unwrap_or_default());
+                if target.symlink_metadata().is_ok() {
+                    return Err(Error::OutputExists { path: target });
+                }
+            }
+        }
+        for volume in &volumes {
+            match self {
+                Disposal::Keep => {}
+                Disposal::Delete => fs::remove_file(volume)?,
+                Disposal::Trash => trash(volume)?,
+                Disposal::MoveTo(dir) => move_into(volume, dir)?,
+            }
+        }
+        Ok(volumes)
+    }
+}
+
+/// `file` and the other volumes of the set it belongs to, found next to it
+/// by name: `NAME.part1.rar`, `NAME.part2.rar`, ... and `NAME.rar`,
+/// `NAME.r00`, ... for RAR and `NAME.zip`, `NAME.z01`, ... for split zips.
+pub fn volumes(file: &Path) -> Result<Vec<PathBuf>> {
+    let name = file.file_name().unwrap_or_default().to_string_lossy();
+    let lowered = name.to_lowercase();
+
+    let is_volume: Box<dyn Fn(&str) -> bool> = if let Some(stem) = lowered
+        .strip_suffix(".rar")
+        .and_then(|rest| rest.rsplit_once(".part"))
+        .filter(|(_, number)| is_number(number))
+        .map(|(stem, _)| format!("{stem}.part"))
+    {
+        Box::new(move |other| {
+            other
+                .strip_prefix(&stem)
+                .and_then(|rest| rest.strip_suffix(".rar"))
+                .is_some_and(is_number)
+        })
+    } else if let Some(stem) = lowered.strip_suffix(".rar") {
+        let stem = format!("{stem}.r");
+        Box::new(move |other| other.strip_prefix(&stem).is_some_and(is_number))
+    } else if let Some(stem) = lowered.strip_suffix(".zip") {
+        let stem = format!("{stem}.z");
+        Box::new(move |other| other.strip_prefix(&stem).is_some_and(is_number))
+    } else {
+        return Ok(vec![file.to_path_buf()]);
+    };
+
+    let dir = match file.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let mut volumes = vec![file.to_path_buf()];
+    for entry in fs::read_dir(dir)? {
+        let other = entry?.file_name().to_string_lossy().into_owned();
+        if other.to_lowercase() != lowered && is_volume(&other.to_lowercase()) {
+            volumes.push(file.with_file_name(other));
+        }
+    }
+    volumes.sort();
+    Ok(volumes)
+}
+
+fn is_number(text: &str) -> bool {
+    !text.is_empty() && text.bytes().all(|byte| byte.is_ascii_digit())
+}
+
+/// Moves `file` into `dir`, copying it when `dir` is on another filesystem.
+fn move_into(file: &Path, dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    let target = dir.join(file.file_name().unwrap_or_default());
+    if target.symlink_metadata().is_ok() {
+        return Err(Error::OutputExists { path: target });
+    }
+
+    if fs::rename(file, &target).is_err() {
+        fs::copy(file, &target)?;
+        fs::remove_file(file)?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn trash(file: &Path) -> Result<()> {
+    freedesktop::trash(file)
+}
+
+#[cfg(not(unix))]
+fn trash(_file: &Path) -> Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "moving to the trash is only supported on freedesktop.org systems",
+    )
+    .into())
+}
+
+#[cfg(unix)]
+mod freedesktop {
+    use crate::error::Result;
+    use std::env;
+    use std::fmt::Write as _;
+    use std::fs::{self, DirBuilder, OpenOptions};
+    use std::io::{self, Write};
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+    use std::path::{Path, PathBuf};
+
+    /// Moves `file` to the home trash, or to the `.Trash-UID` directory at
+    /// the top of its filesystem when that is a different one.
+    pub fn trash(file: &Path) -> Result<()> {
+        let file = env::current_dir()?
+            .join(file)
+            .components()
+            .collect::<PathBuf>();
+        let device = fs::symlink_metadata(&file)?.dev();
+
+        let home_trash = home_trash()?;
+        let (trash, original) = if device_of(&home_trash)? == Some(device) {
+            (home_trash, file.clone())
+        } else {
+            let top = top_dir(&file, device)?;
+            let uid = unsafe { libc::getuid() };
+            let original = file.strip_prefix(&top).unwrap_or(&file).to_path_buf();
+            (top.join(format!(".Trash-{uid}")), original)
+        };
+
+        let files = trash.join("files");
+        let info = trash.join("info");
+        for dir in [&files, &info] {
+            DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+        }
+
+        // Claiming the name with the info file keeps other programs trashing
+        // at the same time from picking it too
+        let name = file.file_name().unwrap_or_default().to_string_lossy();
+        let mut attempt = 1;
+        let (trashed, mut info_file) = loop {
+            let candidate = match attempt {
+                1 => name.to_string(),
+                n => format!("{name}.{n}"),
+            };
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(info.join(format!("{candidate}.trashinfo")))
+            {
+                Ok(info_file) => break (files.join(&candidate), info_file),
+                Err(error) if error.kind() == io::ErrorKind::AlreadyExists => attempt += 1,
+                Err(error) => return Err(error.into()),
+            }
+        };
+
+        write!(
+            info_file,
+            "[Trash Info]\nPath={}\nDeletionDate={}\n",
+            percent_encode(&original),
+            deletion_date()
+        )?;
+        fs::rename(&file, &trashed)?;
+        Ok(())
+    }
+
+    fn home_trash() -> Result<PathBuf> {
+        let data_home = env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .filter(|path| path.is_absolute())
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "HOME is not set"))?;
+        Ok(data_home.join("Trash"))
+    }
+
+    /// The device of `path`, or of its closest existing parent.
+    fn device_of(path: &Path) -> Result<Option<u64>> {
+        for ancestor in path.ancestors() {
+            match fs::metadata(ancestor) {
+                Ok(metadata) => return Ok(Some(metadata.dev())),
+                Err(error) if error.kind() == io::ErrorKind::NotFound => continue,
+                Err(error) => return Err(error.into()),
+            }
+        }
+        Ok(None)
+    }
+
+    /// The mount point of the filesystem `file` is on.
+    fn top_dir(file: &Path, device: u64) -> Result<PathBuf> {
+        let mut top = file.parent().unwrap_or(Path::new("/")).to_path_buf();
+        while let Some(parent) = top.parent() {
+            if fs::metadata(parent)?.dev() != device {
+                break;
+            }
+            top = parent.to_path_buf();
+        }
+        Ok(top)
+    }
+
+    fn percent_encode(path: &Path) -> String {
+        path.as_os_str()
+            .as_bytes()
+            .iter()
+            .fold(String::new(), |mut encoded, &byte| {
+                if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+                    encoded.push(byte as char);
+                } else {
+                    let _ = write!(encoded, "%{byte:02X}");
+                }
+                encoded
+            })
+    }
+
+    /// The current local time as `YYYY-MM-DDThh:mm:ss`.
+    fn deletion_date() -> String {
+        unsafe {
+            let now = libc::time(std::ptr::null_mut());
+            let mut tm = std::mem::zeroed::<libc::tm>();
+            libc::localtime_r(&now, &mut tm);
+            format!(
+                "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+                tm.tm_year + 1900,
+                tm.tm_mon + 1,
+                tm.tm_mday,
+                tm.tm_hour,
+                tm.tm_min,
+                tm.tm_sec
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_other_volumes_of_a_set() {
+        let dir = crate::scratch_dir("dispose-volumes");
+        for name in [
+            "Movie.part01.rar",
+            "movie.part02.rar",
+            "movie.part3.rar",
+            "movie.partx.rar",
+            "other.part02.rar",
+            "set.rar",
+            "set.r00",
+            "set.rev",
+            "data.zip",
+            "data.z01",
+            "data.zipx",
+            "release.tar.gz",
+            "release.tar.gz.1",
+        ] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+        let volume_names = |file: &str| {
+            volumes(&dir.join(file))
+                .unwrap()
+                .iter()
+                .map(|volume| volume.file_name().unwrap().to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            volume_names("Movie.part01.rar"),
+            ["Movie.part01.rar", "movie.part02.rar", "movie.part3.rar"]
+        );
+        assert_eq!(volume_names("set.rar"), ["set.r00", "set.rar"]);
+        assert_eq!(volume_names("data.zip"), ["data.z01", "data.zip"]);
+        assert_eq!(volume_names("release.tar.gz"), ["release.tar.gz"]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,323 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Everything that can go wrong while detecting and extracting archives.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// No extractor is registered for the file
+    UnsupportedFormat { file: PathBuf },
+    /// None of the tools able to extract the format are installed
+    NoToolInstalled { format: String, tools: Vec<String> },
+    /// A tool was found on `PATH`, but outside the trusted system directories
+    UntrustedTool { tool: String, path: PathBuf },
+    /// The requested tool can not extract the format
+    UnsupportedBackend {
+        tool: String,
+        format: String,
+        supported: Vec<String>,
+    },
+    /// The tool ran but did not exit successfully, `code` is `None` when it
+    /// was terminated by a signal
+    BackendFailed {
+        tool: String,
+        code: Option<i32>,
+        stderr: String,
+    },
+    /// The tool failed for a recognised reason
+    ArchiveFailed {
+        file: PathBuf,
+        tool: String,
+        reason: FailureReason,
+    },
+    /// The configuration could not be read or is invalid
+    Config {
+        path: Option<PathBuf>,
+        message: String,
+    },
+    /// Extracting would replace an existing file
+    OutputExists { path: PathBuf },
+    /// An archive did not match its checksum or signature, or files did not
+    /// match their recorded hashes, sizes or modes
+    VerificationFailed {
+        file: PathBuf,
+        problems: Vec<String>,
+    },
+    /// A tool did not finish within the timeout and was stopped
+    TimedOut { tool: String, timeout: Duration },
+    /// Extracting was stopped by a signal such as Ctrl-C, the tools that
+    /// were running have been stopped as well
+    Cancelled { signal: i32 },
+    /// Reading or writing files failed
+    Io(io::Error),
+}
+
+impl Error {
+    /// Stable process exit code for each category of error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Config { .. } | Error::UnsupportedBackend { .. } => 2,
+            Error::UnsupportedFormat { .. } => 3,
+            Error::NoToolInstalled { .. } => 4,
+            Error::UntrustedTool { .. } => 5,
+            Error::BackendFailed { .. } | Error::ArchiveFailed { .. } => 6,
+            Error::OutputExists { .. } | Error::Io(_) => 7,
+            Error::VerificationFailed { .. } => 8,
+            Error::TimedOut { .. } => 9,
+            // Like a shell reports a command killed by the signal
+            Error::Cancelled { signal } => 128 + signal,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::UnsupportedFormat { file } => {
+                write!(f, "no supported extractor for {}", file.display())
+            }
+            Error::NoToolInstalled { format, tools } => write!(
+                f,
+                "no extraction tool installed for .{format} archives, supported tools are: {}",
+                tools.join(", ")
+            ),
+            Error::UntrustedTool { tool, path } => write!(
+                f,
+                "{tool} was found at {}, which is not a trusted system directory",
+                path.display()
+            ),
+            Error::UnsupportedBackend {
+                tool,
+                format,
+                supported,
+            } => write!(
+                f,
+                "{tool} can not be used for .{format} archives, supported tools are: {}",
+                supported.join(", ")
+            ),
+            Error::BackendFailed { tool, code, .. } => match code {
+                Some(code) => write!(f, "{tool} failed with exit status {code}"),
+                None => write!(f, "{tool} was terminated by a signal"),
+            },
+            Error::ArchiveFailed { file, tool, reason } => {
+                write!(f, "{tool} could not extract {}: {reason}", file.display())
+            }
+            Error::Config {
+                path: Some(path),
+                message,
+            } => write!(f, "invalid configuration in {}: {message}", path.display()),
+            Error::Config {
+                path: None,
+                message,
+            } => write!(f, "invalid configuration: {message}"),
+            Error::OutputExists { path } => write!(f, "{} already exists", path.display()),
+            Error::VerificationFailed { file, problems } => write!(
+                f,
+                "verifying {} failed: {}",
+                file.display(),
+                problems.join(", ")
+            ),
+            Error::TimedOut { tool, timeout } => write!(
+                f,
+                "{tool} did not finish within {}s and was stopped",
+                timeout.as_secs_f64()
+            ),
+            Error::Cancelled { signal } => write!(f, "cancelled by signal {signal}"),
+            Error::Io(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+/// Common reasons for a tool to fail, recognised from its error output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FailureReason {
+    WrongPassword,
+    Corrupt,
+    Truncated,
+    UnsupportedMethod,
+    DiskFull,
+    MissingVolume,
+}
+
+impl FailureReason {
+    /// Recognises the failure from the error output of unzip, 7z, unrar,
+    /// tar, gzip and friends.
+    pub fn from_stderr(stderr: &str) -> Option<FailureReason> {
+        const SIGNATURES: &[(FailureReason, &[&str])] = &[
+            (
+                FailureReason::WrongPassword,
+                &[
+                    "wrong password",
+                    "incorrect password",
+                    "password is incorrect",
+                    "unable to get password",
+                    "incorrect passphrase",
+                    "passphrase required",
+                ],
+            ),
+            (
+                FailureReason::DiskFull,
+                &[
+                    "no space left on device",
+                    "not enough space on the disk",
+                    "disk full",
+                ],
+            ),
+            (
+                FailureReason::MissingVolume,
+                &[
+                    "missing volume",
+                    "cannot find volume",
+                    "cannot open volume",
+                    "need next volume",
+                ],
+            ),
+            (
+                FailureReason::UnsupportedMethod,
+                &[
+                    "unsupported method",
+                    "unsupported compression method",
+                    "compression method not supported",
+                    "unknown method",
+                    "need pk compat",
+                    "unsupported zip compression method",
+                ],
+            ),
+            (
+                FailureReason::Corrupt,
+                &[
+                    "crc failed",
+                    "crc error",
+                    "bad crc",
+                    "checksum error",
+                    "data is corrupt",
+                    "corrupt input",
+                    "invalid compressed data",
+                    "not in gzip format",
+                    "headers error",
+                ],
+            ),
+            (
+                FailureReason::Truncated,
+                &[
+                    "unexpected end of",
+                    "unexpected eof",
+                    "ends unexpectedly",
+                    "truncated",
+                    "premature end",
+                ],
+            ),
+        ];
+
+        let stderr = stderr.to_lowercase();
+        SIGNATURES
+            .iter()
+            .find(|(_, patterns)| patterns.iter().any(|pattern| stderr.contains(pattern)))
+            .map(|(reason, _)| *reason)
+    }
+
+    /// What the user can do about it.
+    pub fn suggestion(&self) -> &'static str {
+        match self {
+            FailureReason::WrongPassword => "Check the password and try again.",
+            FailureReason::Corrupt => {
+                "The archive is damaged, try downloading or copying it again."
+            }
+            FailureReason::Truncated => {
+                "The archive is incomplete, make sure the download or copy finished."
+            }
+            FailureReason::UnsupportedMethod => {
+                "Try another tool with --backend, for example 7z, or use --fallback."
+            }
+            FailureReason::DiskFull => {
+                "Free up some space or extract somewhere else with --output-dir."
+            }
+            FailureReason::MissingVolume => {
+                "Put all parts of the multi-volume archive in the same directory."
+            }
+        }
+    }
+}
+
+impl fmt::Display for FailureReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FailureReason::WrongPassword => "wrong password",
+            FailureReason::Corrupt => "the archive is corrupt",
+            FailureReason::Truncated => "unexpected end of archive",
+            FailureReason::UnsupportedMethod => "unsupported compression method",
+            FailureReason::DiskFull => "the disk is full",
+            FailureReason::MissingVolume => "a volume of the archive is missing",
+        })
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognises_tool_messages() {
+        for (stderr, reason) in [
+            (
+                "ERROR: Wrong password : secret.txt",
+                FailureReason::WrongPassword,
+            ),
+            (
+                "secret.txt - checksum error in the encrypted file secret.txt. Corrupt file or wrong password.",
+                FailureReason::WrongPassword,
+            ),
+            (
+                "gzip: stdin: not in gzip format",
+                FailureReason::Corrupt,
+            ),
+            (
+                "tar: Unexpected EOF in archive",
+                FailureReason::Truncated,
+            ),
+            (
+                "cannot write: No space left on device",
+                FailureReason::DiskFull,
+            ),
+            (
+                "skipping: data.bin  need PK compat. v5.1 (can do v4.6)",
+                FailureReason::UnsupportedMethod,
+            ),
+            (
+                "Cannot find volume archive.part2.rar",
+                FailureReason::MissingVolume,
+            ),
+        ] {
+            assert_eq!(FailureReason::from_stderr(stderr), Some(reason), "{stderr}");
+        }
+    }
+
+    #[test]
+    fn leaves_other_messages_alone() {
+        assert_eq!(FailureReason::from_stderr(""), None);
+        assert_eq!(
+            FailureReason::from_stderr("tar: notes.txt: Cannot open: Permission denied"),
+            None
+        );
+    }
+}
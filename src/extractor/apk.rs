@@ -0,0 +1,74 @@
+use super::zip::ZipExtractor;
+use super::{Extractor, FileFormat};
+use crate::error::Result;
+use crate::package;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::process::Command;
+
+/// Alpine Linux packages, concatenated gzipped tarballs holding the
+/// signature, the `.PKGINFO` control data and the payload. Android
+/// packages share the extension but are zip files, those are handed to
+/// [`ZipExtractor`].
+pub struct ApkExtractor;
+
+impl Extractor for ApkExtractor {
+    fn file_extensions(&self) -> Vec<&'static str> {
+        vec!["apk"]
+    }
+
+    fn binary_names(&self) -> Vec<&'static str> {
+        vec!["bsdtar", "tar", "gtar", "unzip", "7z"]
+    }
+
+    fn build_command(
+        &self,
+        binary: &Path,
+        file: &Path,
+        format: &FileFormat,
+        output_dir: &Path,
+        verbose: bool,
+    ) -> Result<Command> {
+        let bin_name = binary.file_name().unwrap().to_str().unwrap();
+
+        if is_zip(file)? {
+            return match bin_name {
+                "bsdtar" | "unzip" | "7z" => {
+                    ZipExtractor.build_command(binary, file, format, output_dir, verbose)
+                }
+                _ => Err(self.unsupported_backend(bin_name)),
+            };
+        }
+
+        let mut cmd = Command::new(binary);
+        match bin_name {
+            "bsdtar" | "tar" | "gtar" => {
+                cmd.arg("-x")
+                    .arg("-z")
+                    .arg("-f")
+                    .arg(file)
+                    .arg("-C")
+                    .arg(output_dir);
+                if verbose {
+                    cmd.arg("-v");
+                }
+            }
+            _ => return Err(self.unsupported_backend(bin_name)),
+        }
+        Ok(cmd)
+    }
+
+    fn after_extract(&self, file: &Path, output_dir: &Path, _verbose: bool) -> Result<()> {
+        if is_zip(file)? {
+            return Ok(());
+        }
+        package::move_root_meta(output_dir)
+    }
+}
+
+fn is_zip(file: &Path) -> Result<bool> {
+    let mut magic = [0u8; 4];
+    let read = File::open(file)?.read(&mut magic)?;
+    Ok(read == magic.len() && &magic == b"PK\x03\x04")
+}
@@ -0,0 +1,147 @@
+use super::{Extractor, FileFormat};
+use crate::error::{Error, Result};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::process::Command;
+
+/// AppImages are an ELF runtime followed by a squashfs image (type 2) or
+/// an ISO 9660 image that is also an ELF binary (type 1).
+pub struct AppImageExtractor;
+
+impl Extractor for AppImageExtractor {
+    fn file_extensions(&self) -> Vec<&'static str> {
+        vec!["appimage"]
+    }
+
+    fn binary_names(&self) -> Vec<&'static str> {
+        vec!["unsquashfs", "bsdtar", "7z"]
+    }
+
+    fn build_command(
+        &self,
+        binary: &Path,
+        file: &Path,
+        _format: &FileFormat,
+        output_dir: &Path,
+        verbose: bool,
+    ) -> Result<Command> {
+        let mut cmd = Command::new(binary);
+        let bin_name = binary.file_name().unwrap().to_str().unwrap();
+
+        match bin_name {
+            "unsquashfs" => {
+                let offset = squashfs_offset(file)?;
+                cmd.arg("-f")
+                    .arg("-d")
+                    .arg(output_dir)
+                    .arg("-o")
+                    .arg(offset.to_string());
+                if !verbose {
+                    cmd.arg("-q").arg("-no-progress");
+                }
+                cmd.arg(file);
+            }
+            "bsdtar" => {
+                cmd.arg("-xf").arg(file).arg("-C").arg(output_dir);
+                if verbose {
+                    cmd.arg("-v");
+                }
+            }
+            "7z" => {
+                cmd.arg("x")
+                    .arg("-y")
+                    .arg("-tiso")
+                    .arg(format!("-o{}", output_dir.display()))
+                    .arg(file);
+                if !verbose {
+                    cmd.arg("-bso0").arg("-bd");
+                }
+            }
+            _ => return Err(self.unsupported_backend(bin_name)),
+        }
+        Ok(cmd)
+    }
+}
+
+const SQUASHFS_MAGIC: &[u8; 4] = b"hsqs";
+
+/// Finds where the squashfs image starts in a type 2 AppImage. That is
+/// normally right after the ELF section headers, otherwise the file is
+/// searched for the squashfs magic.
+pub fn squashfs_offset(file: &Path) -> Result<u64> {
+    let not_found = || Error::UnsupportedFormat {
+        file: file.to_path_buf(),
+    };
+    let mut reader = BufReader::new(File::open(file)?);
+
+    let mut header = [0u8; 64];
+    reader.read_exact(&mut header)?;
+    if &header[..4] != b"\x7fELF" {
+        return Err(not_found());
+    }
+
+    let little_endian = header[5] == 1;
+    let u16_at = |at: usize| {
+        let bytes = [header[at], header[at + 1]];
+        u64::from(if little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    };
+    let u32_at = |at: usize| {
+        let bytes = header[at..at + 4].try_into().unwrap();
+        u64::from(if little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    };
+    let u64_at = |at: usize| {
+        let bytes = header[at..at + 8].try_into().unwrap();
+        if little_endian {
+            u64::from_le_bytes(bytes)
+        } else {
+            u64::from_be_bytes(bytes)
+        }
+    };
+
+    // e_shoff + e_shentsize * e_shnum, at different places for 32 and 64 bit
+    let (shoff, shentsize, shnum) = match header[4] {
+        1 => (u32_at(0x20), u16_at(0x2e), u16_at(0x30)),
+        2 => (u64_at(0x28), u16_at(0x3a), u16_at(0x3c)),
+        _ => return Err(not_found()),
+    };
+    let end_of_elf = shentsize
+        .checked_mul(shnum)
+        .and_then(|size| size.checked_add(shoff))
+        .ok_or_else(not_found)?;
+
+    let mut magic = [0u8; 4];
+    reader.seek(SeekFrom::Start(end_of_elf))?;
+    if reader.read_exact(&mut magic).is_ok() && &magic == SQUASHFS_MAGIC {
+        return Ok(end_of_elf);
+    }
+
+    reader.seek(SeekFrom::Start(0))?;
+    let mut window = Vec::new();
+    let mut offset = 0u64;
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        let read = reader.read(&mut buf)?;
+        if read == 0 {
+            return Err(not_found());
+        }
+        window.extend_from_slice(&buf[..read]);
+        if let Some(found) = window
+            .windows(SQUASHFS_MAGIC.len())
+            .position(|candidate| candidate == SQUASHFS_MAGIC)
+        {
+            return Ok(offset + found as u64);
+        }
+        let keep = SQUASHFS_MAGIC.len() - 1;
+        offset += (window.len() - keep) as u64;
+        window.drain(..window.len() - keep);
+    }
+}
@@ -0,0 +1,41 @@
+use super::tar::TarExtractor;
+use super::{Extractor, FileFormat};
+use crate::error::Result;
+use crate::package;
+use std::path::Path;
+use std::process::Command;
+
+/// Arch Linux packages, compressed tarballs with `.PKGINFO`, `.BUILDINFO`
+/// and `.MTREE` next to the payload.
+pub struct ArchPkgExtractor;
+
+impl Extractor for ArchPkgExtractor {
+    fn file_extensions(&self) -> Vec<&'static str> {
+        vec![
+            "pkg.tar",
+            "pkg.tar.zst",
+            "pkg.tar.xz",
+            "pkg.tar.gz",
+            "pkg.tar.bz2",
+        ]
+    }
+
+    fn binary_names(&self) -> Vec<&'static str> {
+        vec!["bsdtar", "tar", "gtar"]
+    }
+
+    fn build_command(
+        &self,
+        binary: &Path,
+        file: &Path,
+        format: &FileFormat,
+        output_dir: &Path,
+        verbose: bool,
+    ) -> Result<Command> {
+        TarExtractor.build_command(binary, file, format, output_dir, verbose)
+    }
+
+    fn after_extract(&self, _file: &Path, output_dir: &Path, _verbose: bool) -> Result<()> {
+        package::move_root_meta(output_dir)
+    }
+}
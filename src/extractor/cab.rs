@@ -1,5 +1,5 @@
-use super::Extractor;
-use anyhow::Result;
+use super::{Extractor, FileFormat};
+use crate::error::Result;
 use std::path::Path;
 use std::process::Command;
 
@@ -18,6 +18,7 @@ impl Extractor for CabExtractor {
         &self,
         binary: &Path,
         file: &Path,
+        _format: &FileFormat,
         output_dir: &Path,
         verbose: bool,
     ) -> Result<Command> {
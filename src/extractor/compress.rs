@@ -1,5 +1,5 @@
-use super::Extractor;
-use anyhow::Result;
+use super::{Extractor, FileFormat};
+use crate::error::Result;
 use std::path::Path;
 use std::process::Command;
 
@@ -7,34 +7,35 @@ pub struct CompressExtractor;
 
 impl Extractor for CompressExtractor {
     fn file_extensions(&self) -> Vec<&'static str> {
-        vec!["Z"]
+        vec!["z"]
     }
 
     fn binary_names(&self) -> Vec<&'static str> {
         vec!["uncompress"]
     }
 
+    fn is_single_stream(&self) -> bool {
+        true
+    }
+
     fn build_command(
         &self,
         binary: &Path,
         file: &Path,
-        output_dir: &Path,
+        _format: &FileFormat,
+        _output_dir: &Path,
         verbose: bool,
     ) -> Result<Command> {
         let mut cmd = Command::new(binary);
 
-        std::fs::create_dir_all(output_dir)?;
-
-        let target_file = output_dir.join(file.file_name().unwrap());
-        std::fs::copy(file, &target_file)?;
-
-        cmd.current_dir(output_dir);
-        cmd.arg(target_file);
+        cmd.arg("-c");
 
         if verbose {
-            println!("Extracting {} to {}", file.display(), output_dir.display());
+            cmd.arg("-v");
         }
 
+        cmd.arg(file);
+
         Ok(cmd)
     }
 }
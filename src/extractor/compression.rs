@@ -0,0 +1,299 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::process::Command;
+
+use crate::error::Result;
+use crate::extractor::pkg::require_binary;
+use crate::trust::{NativeDecoder, Pipeline};
+
+/// Compressors and encodings that wrap a single stream, such as a tarball
+/// or a cpio archive. A file can be wrapped in several of them, like a
+/// base64 encoded `.tar.gz`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Bzip2,
+    Xz,
+    Lzma,
+    Zstd,
+    Lzip,
+    Lzop,
+    Lz4,
+    Compress,
+    Brotli,
+    Snappy,
+    Base64,
+}
+
+impl Compression {
+    /// Recognises the compressor from the first bytes of a stream.
+    pub fn from_magic(bytes: &[u8]) -> Option<Compression> {
+        const MAGIC: &[(&[u8], Compression)] = &[
+            (&[0x1f, 0x8b], Compression::Gzip),
+            (b"BZh", Compression::Bzip2),
+            (&[0xfd, b'7', b'z', b'X', b'Z', 0x00], Compression::Xz),
+            (&[0x28, 0xb5, 0x2f, 0xfd], Compression::Zstd),
+            (b"LZIP", Compression::Lzip),
+            (
+                &[0x89, b'L', b'Z', b'O', 0x00, 0x0d, 0x0a, 0x1a, 0x0a],
+                Compression::Lzop,
+            ),
+            (&[0x04, 0x22, 0x4d, 0x18], Compression::Lz4),
+            (&[0x02, 0x21, 0x4c, 0x18], Compression::Lz4),
+            (&[0x5d, 0x00, 0x00], Compression::Lzma),
+            (&[0x1f, 0x9d], Compression::Compress),
+            (
+                &[0xff, 0x06, 0x00, 0x00, b's', b'N', b'a', b'P', b'p', b'Y'],
+                Compression::Snappy,
+            ),
+        ];
+
+        MAGIC
+            .iter()
+            .find(|(magic, _)| bytes.starts_with(magic))
+            .map(|(_, compression)| *compression)
+    }
+
+    /// Recognises the compressor from a lowercased file extension, either a
+    /// plain one such as `gz` or a tarball shorthand such as `tgz`.
+    pub fn from_extension(extension: &str) -> Option<Compression> {
+        match extension {
+            "gz" | "tgz" => Some(Compression::Gzip),
+            "bz2" | "bz" | "tbz" | "tbz2" | "tb2" => Some(Compression::Bzip2),
+            "xz" | "txz" => Some(Compression::Xz),
+            "lzma" => Some(Compression::Lzma),
+            "zst" | "tzst" => Some(Compression::Zstd),
+            "lz" | "tlz" => Some(Compression::Lzip),
+            "lzo" => Some(Compression::Lzop),
+            "lz4" => Some(Compression::Lz4),
+            "z" | "taz" => Some(Compression::Compress),
+            "br" => Some(Compression::Brotli),
+            "sz" => Some(Compression::Snappy),
+            "b64" | "base64" => Some(Compression::Base64),
+            _ => None,
+        }
+    }
+
+    /// Reads the first bytes of `file` at `offset` and recognises the
+    /// compressor from them.
+    pub fn detect(file: &Path, offset: u64) -> Result<Option<Compression>> {
+        use std::io::{Seek, SeekFrom};
+
+        let mut reader = File::open(file)?;
+        reader.seek(SeekFrom::Start(offset))?;
+        let mut magic = [0u8; 16];
+        let read = reader.read(&mut magic)?;
+        Ok(Compression::from_magic(&magic[..read]))
+    }
+
+    /// The usual file extension of the compressed stream.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Compression::Gzip => "gz",
+            Compression::Bzip2 => "bz2",
+            Compression::Xz => "xz",
+            Compression::Lzma => "lzma",
+            Compression::Zstd => "zst",
+            Compression::Lzip => "lz",
+            Compression::Lzop => "lzo",
+            Compression::Lz4 => "lz4",
+            Compression::Compress => "Z",
+            Compression::Brotli => "br",
+            Compression::Snappy => "sz",
+            Compression::Base64 => "b64",
+        }
+    }
+
+    /// Tools able to decompress the stream, most preferred first. Empty for
+    /// layers that are always decoded natively.
+    pub fn binary_names(&self) -> &'static [&'static str] {
+        match self {
+            Compression::Gzip => &["gzip", "pigz"],
+            Compression::Bzip2 => &["bzip2", "pbzip2", "lbzip2"],
+            Compression::Xz => &["xz"],
+            Compression::Lzma => &["xz", "lzma"],
+            Compression::Zstd => &["zstd"],
+            Compression::Lzip => &["lzip", "plzip", "lunzip"],
+            Compression::Lzop => &["lzop"],
+            Compression::Lz4 => &["lz4"],
+            Compression::Compress => &["uncompress", "gzip"],
+            Compression::Brotli => &["brotli"],
+            Compression::Snappy => &["snzip"],
+            Compression::Base64 => &[],
+        }
+    }
+
+    /// The built-in decoder for this layer and its name in errors, if extr
+    /// has one.
+    pub fn native_decoder(&self) -> Option<(&'static str, NativeDecoder)> {
+        match self {
+            Compression::Base64 => Some(("built-in base64", |source| {
+                Box::new(Base64Reader::new(source))
+            })),
+            _ => None,
+        }
+    }
+
+    /// A command that decompresses stdin to stdout with `binary`, one of
+    /// [`Compression::binary_names`].
+    pub fn decompress_command(&self, binary: &Path) -> Command {
+        let mut cmd = Command::new(binary);
+        if *self == Compression::Lzma && binary.ends_with("xz") {
+            cmd.arg("--format=lzma");
+        }
+        // The un* variants only decompress and have no -d
+        if !binary.ends_with("uncompress") && !binary.ends_with("lunzip") {
+            cmd.arg("-d");
+        }
+        cmd.arg("-c");
+        cmd
+    }
+}
+
+/// A pipeline that peels `layers` off `file`, outermost first, writing the
+/// innermost stream to stdout.
+pub fn decoder_pipeline(layers: &[Compression], file: &Path) -> Result<Pipeline> {
+    let mut pipeline: Option<Pipeline> = None;
+
+    for layer in layers {
+        pipeline = Some(match (layer.native_decoder(), pipeline) {
+            (Some((name, decode)), None) => Pipeline::native(name, decode),
+            (Some((name, decode)), Some(pipeline)) => pipeline.pipe_native(name, decode),
+            (None, pipeline) => {
+                let binary = require_binary(layer.extension(), layer.binary_names())?;
+                let cmd = layer.decompress_command(&binary);
+                match pipeline {
+                    Some(pipeline) => pipeline.pipe(cmd),
+                    None => Pipeline::new(cmd),
+                }
+            }
+        });
+    }
+
+    let pipeline = pipeline
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no layers to decode"))?;
+    Ok(pipeline.input(file, 0))
+}
+
+/// Decodes base64 while reading, ignoring line breaks and other whitespace.
+/// Concatenated padded blocks, as written by some encoders, are accepted.
+struct Base64Reader {
+    inner: Box<dyn Read + Send>,
+    decoded: Vec<u8>,
+    position: usize,
+    quad: [u8; 4],
+    filled: usize,
+    done: bool,
+}
+
+impl Base64Reader {
+    fn new(inner: Box<dyn Read + Send>) -> Self {
+        Self {
+            inner,
+            decoded: Vec::new(),
+            position: 0,
+            quad: [0; 4],
+            filled: 0,
+            done: false,
+        }
+    }
+
+    fn fill(&mut self) -> io::Result<()> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        let mut buf = [0u8; 8192];
+        let read = self.inner.read(&mut buf)?;
+        self.decoded.clear();
+        self.position = 0;
+
+        if read == 0 {
+            self.done = true;
+            if self.filled != 0 {
+                return Err(invalid("base64 input ends in the middle of a block"));
+            }
+            return Ok(());
+        }
+
+        for &byte in &buf[..read] {
+            let value = match byte {
+                b'A'..=b'Z' => byte - b'A',
+                b'a'..=b'z' => byte - b'a' + 26,
+                b'0'..=b'9' => byte - b'0' + 52,
+                b'+' | b'-' => 62,
+                b'/' | b'_' => 63,
+                b'=' => 64,
+                byte if byte.is_ascii_whitespace() => continue,
+                _ => return Err(invalid("invalid character in base64 input")),
+            };
+            self.quad[self.filled] = value;
+            self.filled += 1;
+            if self.filled < 4 {
+                continue;
+            }
+            self.filled = 0;
+
+            let [a, b, c, d] = self.quad;
+            if a == 64 || b == 64 || (c == 64 && d != 64) {
+                return Err(invalid("misplaced padding in base64 input"));
+            }
+            self.decoded.push(a << 2 | b >> 4);
+            if c != 64 {
+                self.decoded.push(b << 4 | c >> 2);
+            }
+            if d != 64 {
+                self.decoded.push(c << 6 | d);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Read for Base64Reader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.decoded.len() {
+            if self.done {
+                return Ok(0);
+            }
+            self.fill()?;
+        }
+
+        let available = &self.decoded[self.position..];
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(encoded: &str) -> io::Result<Vec<u8>> {
+        let mut decoded = Vec::new();
+        Base64Reader::new(Box::new(io::Cursor::new(encoded.as_bytes().to_vec())))
+            .read_to_end(&mut decoded)?;
+        Ok(decoded)
+    }
+
+    #[test]
+    fn decodes_padded_and_wrapped_input() {
+        assert_eq!(decode("aGVsbG8=").unwrap(), b"hello");
+        assert_eq!(decode("aGVs\nbG8h\r\n").unwrap(), b"hello!");
+        assert_eq!(decode("aGk=aGk=").unwrap(), b"hihi");
+        assert_eq!(decode("").unwrap(), b"");
+        assert_eq!(decode("-_8=").unwrap(), decode("+/8=").unwrap());
+    }
+
+    #[test]
+    fn refuses_malformed_input() {
+        for encoded in ["aGVsbG8", "aG*s", "=GVs", "aG=s"] {
+            assert_eq!(
+                decode(encoded).unwrap_err().kind(),
+                io::ErrorKind::InvalidData,
+                "{encoded}"
+            );
+        }
+    }
+}
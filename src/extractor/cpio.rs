@@ -0,0 +1,228 @@
+use super::compression::{decoder_pipeline, Compression};
+use super::{Extractor, FileFormat};
+use crate::error::{Error, Result};
+use crate::probe::{probe_tool, Flavor};
+use crate::trust::Pipeline;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::process::Command;
+
+/// Plain cpio archives and Linux initramfs images. An initramfs is often
+/// several archives back to back, such as uncompressed CPU microcode
+/// followed by the compressed root filesystem, each one is extracted into
+/// the same directory.
+pub struct CpioExtractor;
+
+impl Extractor for CpioExtractor {
+    fn file_extensions(&self) -> Vec<&'static str> {
+        vec!["cpio", "initramfs", "initrd"]
+    }
+
+    fn binary_names(&self) -> Vec<&'static str> {
+        vec!["cpio", "bsdtar", "7z"]
+    }
+
+    fn build_command(
+        &self,
+        binary: &Path,
+        file: &Path,
+        _format: &FileFormat,
+        output_dir: &Path,
+        verbose: bool,
+    ) -> Result<Command> {
+        let mut cmd = Command::new(binary);
+        let bin_name = binary.file_name().unwrap().to_str().unwrap();
+
+        // cpio and bsdtar read the archive from stdin, build_pipelines feeds
+        // them one segment of the file at a time
+        match bin_name {
+            "cpio" => {
+                cmd.arg("-idm")
+                    .args(confine_args(binary))
+                    .current_dir(output_dir);
+                if verbose {
+                    cmd.arg("-v");
+                }
+            }
+            "bsdtar" => {
+                cmd.arg("-x").arg("-f").arg("-").arg("-C").arg(output_dir);
+                if verbose {
+                    cmd.arg("-v");
+                }
+            }
+            "7z" => {
+                cmd.arg("x")
+                    .arg("-y")
+                    .arg(format!("-o{}", output_dir.display()))
+                    .arg(file);
+                if !verbose {
+                    cmd.arg("-bso0").arg("-bd");
+                }
+            }
+            _ => return Err(self.unsupported_backend(bin_name)),
+        }
+        Ok(cmd)
+    }
+
+    fn build_pipelines(
+        &self,
+        binary: &Path,
+        file: &Path,
+        format: &FileFormat,
+        output_dir: &Path,
+        verbose: bool,
+    ) -> Result<Vec<Pipeline>> {
+        if binary.ends_with("7z") {
+            return Ok(vec![self
+                .build_command(binary, file, format, output_dir, verbose)?
+                .into()]);
+        }
+
+        let mut pipelines = Vec::new();
+        for (offset, compression) in segments(file)? {
+            let extract = self.build_command(binary, file, format, output_dir, verbose)?;
+
+            // bsdtar recognises the compression by itself
+            let pipeline = match compression {
+                Some(compression) if binary.ends_with("cpio") => {
+                    decoder_pipeline(&[compression], file)?.pipe(extract)
+                }
+                _ => Pipeline::new(extract),
+            };
+            pipelines.push(pipeline.input(file, offset));
+        }
+        Ok(pipelines)
+    }
+
+    fn stdin_binary_names(&self) -> Vec<&'static str> {
+        vec!["cpio", "bsdtar"]
+    }
+
+    fn build_stdin_command(
+        &self,
+        binary: &Path,
+        format: &FileFormat,
+        output_dir: &Path,
+        verbose: bool,
+    ) -> Result<Command> {
+        // Both read the archive from stdin already
+        self.build_command(binary, Path::new("-"), format, output_dir, verbose)
+    }
+}
+
+/// Options keeping `cpio` from writing members with absolute names or `..`
+/// outside the current directory. GNU cpio needs to be told, bsdcpio
+/// refuses them unless it is given `--insecure`.
+pub(crate) fn confine_args(binary: &Path) -> &'static [&'static str] {
+    let name = binary.file_name().unwrap_or_default().to_string_lossy();
+    match probe_tool(&name) {
+        Some(info) if info.flavor == Flavor::Bsdcpio => &[],
+        // Tools of unknown make fail on the option rather than write
+        // anywhere
+        _ => &["--no-absolute-filenames"],
+    }
+}
+
+const NEWC_HEADER_LEN: u64 = 110;
+const TRAILER: &[u8] = b"TRAILER!!!\0";
+
+/// Splits `file` into the archives it is made of, as their offsets and the
+/// compression of each one. Uncompressed `newc` archives are walked to their
+/// trailer to find where the next one starts, a compressed archive always
+/// runs to the end of the file.
+fn segments(file: &Path) -> Result<Vec<(u64, Option<Compression>)>> {
+    let len = std::fs::metadata(file)?.len();
+    let mut reader = BufReader::new(File::open(file)?);
+    let mut segments = Vec::new();
+    let mut offset = 0;
+
+    while offset < len {
+        reader.seek(SeekFrom::Start(offset))?;
+        let mut magic = [0u8; 16];
+        let read = read_full(&mut reader, &mut magic)?;
+
+        if let Some(compression) = Compression::from_magic(&magic[..read]) {
+            segments.push((offset, Some(compression)));
+            break;
+        }
+
+        segments.push((offset, None));
+        if !magic.starts_with(b"070701") && !magic.starts_with(b"070702") {
+            // Old formats are left to the tool to make sense of
+            break;
+        }
+
+        offset = skip_newc_archive(&mut reader, offset, file)?;
+
+        // Archives are padded with zeros to a block boundary
+        reader.seek(SeekFrom::Start(offset))?;
+        let mut byte = [0u8; 1];
+        while offset < len {
+            reader.read_exact(&mut byte)?;
+            if byte[0] != 0 {
+                break;
+            }
+            offset += 1;
+        }
+    }
+
+    if segments.is_empty() {
+        return Err(Error::UnsupportedFormat {
+            file: file.to_path_buf(),
+        });
+    }
+    Ok(segments)
+}
+
+/// Walks the `newc` entries starting at `offset` and returns the offset just
+/// past the trailer entry.
+fn skip_newc_archive(reader: &mut BufReader<File>, mut offset: u64, file: &Path) -> Result<u64> {
+    let corrupt = || Error::UnsupportedFormat {
+        file: file.to_path_buf(),
+    };
+
+    loop {
+        reader.seek(SeekFrom::Start(offset))?;
+        let mut header = [0u8; NEWC_HEADER_LEN as usize];
+        reader.read_exact(&mut header)?;
+        if !header.starts_with(b"070701") && !header.starts_with(b"070702") {
+            return Err(corrupt());
+        }
+
+        let field = |index: usize| {
+            let start = 6 + index * 8;
+            std::str::from_utf8(&header[start..start + 8])
+                .ok()
+                .and_then(|hex| u64::from_str_radix(hex, 16).ok())
+                .ok_or_else(corrupt)
+        };
+        let file_size = field(6)?;
+        let name_size = field(11)?;
+
+        let mut name = vec![0u8; name_size as usize];
+        reader.read_exact(&mut name)?;
+
+        offset = align4(offset + NEWC_HEADER_LEN + name_size);
+        offset = align4(offset + file_size);
+
+        if name == TRAILER {
+            return Ok(offset);
+        }
+    }
+}
+
+fn align4(offset: u64) -> u64 {
+    (offset + 3) & !3
+}
+
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+    Ok(read)
+}
@@ -0,0 +1,230 @@
+use super::{register_extractor, Extractor, FileFormat};
+use crate::config::ExtractorConfig;
+use crate::error::{Error, Result};
+use crate::trust::Pipeline;
+use std::collections::BTreeMap;
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+/// How many bytes at the start of a file magic bytes can be matched in.
+pub const MAX_MAGIC_END: usize = 4096;
+
+/// Environment variable `{password}` placeholders are filled from.
+pub const PASSWORD_VAR: &str = "EXTR_PASSWORD";
+
+/// A format declared in the `[extractors]` section of the configuration.
+/// Each tool is run in the output directory with the arguments from its
+/// template, where `{file}` is the archive, `{out}` the output directory and
+/// `{password}` the value of `$EXTR_PASSWORD`. Arguments with a password
+/// placeholder are left out when no password is set, or when it is typed
+/// into the tool with `password_stdin`, where `ps` cannot see it.
+/// `$EXTR_PASSWORD` is in the environment of every tool as well.
+#[derive(Debug)]
+pub struct CustomExtractor {
+    extensions: Vec<String>,
+    magic: Vec<Vec<u8>>,
+    magic_offset: usize,
+    binaries: Vec<&'static str>,
+    args: BTreeMap<String, Vec<String>>,
+    password_stdin: bool,
+}
+
+impl CustomExtractor {
+    /// Checks the configuration of the extractor called `name`.
+    pub fn from_config(name: &str, config: &ExtractorConfig) -> Result<Self> {
+        let invalid = |message: String| Error::Config {
+            path: None,
+            message: format!("extractors.{name}: {message}"),
+        };
+
+        if config.extensions.is_empty() {
+            return Err(invalid("at least one extension is required".to_string()));
+        }
+        if config.binaries.is_empty() {
+            return Err(invalid("at least one binary is required".to_string()));
+        }
+        if let Some(binary) = config
+            .binaries
+            .iter()
+            .find(|binary| !config.args.contains_key(*binary))
+        {
+            return Err(invalid(format!("no args template for {binary}")));
+        }
+
+        let magic = config
+            .magic
+            .iter()
+            .map(|hex| {
+                parse_hex(hex).ok_or_else(|| invalid(format!("invalid magic bytes {hex:?}")))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let magic_offset = usize::try_from(config.magic_offset).unwrap_or(usize::MAX);
+        if magic.iter().any(|bytes| {
+            bytes.is_empty()
+                || magic_offset
+                    .checked_add(bytes.len())
+                    .map_or(true, |end| end > MAX_MAGIC_END)
+        }) {
+            return Err(invalid(format!(
+                "magic bytes must be non-empty and end within the first {MAX_MAGIC_END} bytes"
+            )));
+        }
+
+        Ok(Self {
+            extensions: config
+                .extensions
+                .iter()
+                .map(|ext| ext.trim_start_matches('.').to_lowercase())
+                .collect(),
+            magic,
+            magic_offset,
+            // The registry hands out tool names for the lifetime of the
+            // program, custom extractors are only loaded once at startup
+            binaries: config
+                .binaries
+                .iter()
+                .map(|binary| &*Box::leak(binary.clone().into_boxed_str()))
+                .collect(),
+            args: config.args.clone(),
+            password_stdin: config.password_stdin,
+        })
+    }
+}
+
+impl Extractor for CustomExtractor {
+    fn file_extensions(&self) -> Vec<&str> {
+        self.extensions.iter().map(String::as_str).collect()
+    }
+
+    fn binary_names(&self) -> Vec<&'static str> {
+        self.binaries.clone()
+    }
+
+    fn matches_header(&self, header: &[u8]) -> bool {
+        self.magic.iter().any(|magic| {
+            header
+                .get(self.magic_offset..)
+                .is_some_and(|rest| rest.starts_with(magic))
+        })
+    }
+
+    fn build_command(
+        &self,
+        binary: &Path,
+        file: &Path,
+        _format: &FileFormat,
+        output_dir: &Path,
+        _verbose: bool,
+    ) -> Result<Command> {
+        let bin_name = binary.file_name().unwrap().to_str().unwrap();
+        let Some(template) = self.args.get(bin_name) else {
+            return Err(self.unsupported_backend(bin_name));
+        };
+
+        // The tool runs in the output directory, relative paths would no
+        // longer point at the right place
+        let current_dir = env::current_dir()?;
+        let file = current_dir.join(file);
+        let output_dir = current_dir.join(output_dir);
+        let password = env::var(PASSWORD_VAR).ok();
+
+        let mut cmd = Command::new(binary);
+        cmd.current_dir(&output_dir);
+        for arg in template {
+            if arg.contains("{password}") && (password.is_none() || self.password_stdin) {
+                continue;
+            }
+            cmd.arg(
+                arg.replace("{file}", &file.to_string_lossy())
+                    .replace("{out}", &output_dir.to_string_lossy())
+                    .replace("{password}", password.as_deref().unwrap_or_default()),
+            );
+        }
+        Ok(cmd)
+    }
+
+    fn build_pipelines(
+        &self,
+        binary: &Path,
+        file: &Path,
+        format: &FileFormat,
+        output_dir: &Path,
+        verbose: bool,
+    ) -> Result<Vec<Pipeline>> {
+        let pipeline =
+            Pipeline::new(self.build_command(binary, file, format, output_dir, verbose)?);
+        // Keeps the password out of --dry-run output
+        Ok(vec![match env::var(PASSWORD_VAR) {
+            Ok(password) if self.password_stdin => {
+                pipeline.feed_line(password.clone()).redact(password)
+            }
+            Ok(password) => pipeline.redact(password),
+            Err(_) => pipeline,
+        }])
+    }
+}
+
+/// Registers the extractors declared in the configuration, replacing
+/// built-in ones for the same extensions.
+pub fn register_custom_extractors(extractors: &BTreeMap<String, ExtractorConfig>) -> Result<()> {
+    let extractors = extractors
+        .iter()
+        .map(|(name, config)| CustomExtractor::from_config(name, config))
+        .collect::<Result<Vec<_>>>()?;
+
+    for extractor in extractors {
+        register_extractor(Box::leak(Box::new(extractor)));
+    }
+    Ok(())
+}
+
+/// Bytes written as hex, with optional whitespace between them, e.g.
+/// `"4c 5a 58"` or `"4c5a58"`.
+fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+    let digits = hex
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<Vec<_>>();
+    if digits.len() % 2 != 0 {
+        return None;
+    }
+
+    digits
+        .chunks(2)
+        .map(|pair| {
+            let pair = pair.iter().collect::<String>();
+            u8::from_str_radix(&pair, 16).ok()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(magic_offset: u64) -> ExtractorConfig {
+        ExtractorConfig {
+            extensions: vec!["lzx".to_string()],
+            magic: vec!["4c 5a 58".to_string()],
+            magic_offset,
+            binaries: vec!["unlzx".to_string()],
+            args: BTreeMap::from([("unlzx".to_string(), vec!["{file}".to_string()])]),
+            password_stdin: false,
+        }
+    }
+
+    #[test]
+    fn keeps_magic_bytes_near_the_start() {
+        assert!(CustomExtractor::from_config("lzx", &config(0)).is_ok());
+        let last = (MAX_MAGIC_END - 3) as u64;
+        assert!(CustomExtractor::from_config("lzx", &config(last)).is_ok());
+
+        for offset in [last + 1, u64::MAX] {
+            assert!(matches!(
+                CustomExtractor::from_config("lzx", &config(offset)),
+                Err(Error::Config { .. })
+            ));
+        }
+    }
+}
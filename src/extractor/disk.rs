@@ -0,0 +1,149 @@
+use super::{Extractor, FileFormat};
+use crate::error::{Error, Result};
+use crate::trust::run_command;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Staging directory inside the output directory that 7z writes to, its
+/// contents are sorted into the output directory by `after_extract`.
+const STAGING_DIR: &str = ".extr-disk";
+
+/// Extensions 7z gives partitions and the raw disks inside virtual disk
+/// containers.
+const PARTITION_EXTENSIONS: &[&str] = &[
+    "img", "mbr", "gpt", "apm", "fat", "ntfs", "ext", "hfs", "hfsx", "apfs", "squashfs", "iso",
+    "udf", "raw",
+];
+
+/// How many levels of disk, partition table and partition are unpacked.
+const MAX_DEPTH: usize = 3;
+
+/// Disk images and virtual machine disks. Each partition found on the disk
+/// is extracted into a subdirectory named after it, partitions 7z can not
+/// read are kept as image files.
+pub struct DiskImageExtractor;
+
+impl Extractor for DiskImageExtractor {
+    fn file_extensions(&self) -> Vec<&'static str> {
+        vec!["img", "dmg", "vhd", "vhdx", "vmdk", "vdi", "qcow", "qcow2"]
+    }
+
+    fn binary_names(&self) -> Vec<&'static str> {
+        vec!["7z"]
+    }
+
+    fn build_command(
+        &self,
+        binary: &Path,
+        file: &Path,
+        _format: &FileFormat,
+        output_dir: &Path,
+        verbose: bool,
+    ) -> Result<Command> {
+        let bin_name = binary.file_name().unwrap().to_str().unwrap();
+        if bin_name != "7z" {
+            return Err(self.unsupported_backend(bin_name));
+        }
+
+        let mut cmd = Command::new(binary);
+        cmd.arg("x")
+            .arg("-y")
+            .arg(format!("-o{}", output_dir.join(STAGING_DIR).display()))
+            .arg(file);
+        if !verbose {
+            cmd.arg("-bso0").arg("-bd");
+        }
+        Ok(cmd)
+    }
+
+    fn after_extract(&self, _file: &Path, output_dir: &Path, verbose: bool) -> Result<()> {
+        let staging = output_dir.join(STAGING_DIR);
+        let binary = self.get_verified_binary(&["7z"])?;
+        let result = unpack_partitions(&binary, &staging, output_dir, verbose, MAX_DEPTH);
+        let _ = fs::remove_dir_all(&staging);
+        result
+    }
+}
+
+/// Moves everything in `from` to `to`, extracting partition images into
+/// subdirectories of `to` on the way. Nothing in `to` is replaced, the
+/// overwrite policy is applied when the staged output is put in place.
+fn unpack_partitions(
+    binary: &Path,
+    from: &Path,
+    to: &Path,
+    verbose: bool,
+    depth: usize,
+) -> Result<()> {
+    let entries = fs::read_dir(from)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+
+    for path in entries {
+        let name = path.file_name().unwrap().to_os_string();
+
+        if depth > 0 && is_partition(&path) {
+            let target = partition_dir(&path, to);
+            refuse_existing(&target)?;
+            let staging = from.join(format!("{}.parts", name.to_string_lossy()));
+            let mut cmd = Command::new(binary);
+            cmd.arg("x")
+                .arg("-y")
+                .arg(format!("-o{}", staging.display()))
+                .arg(&path);
+            if !verbose {
+                cmd.arg("-bso0").arg("-bd");
+            }
+
+            match run_command(cmd, verbose) {
+                Ok(()) => {
+                    fs::create_dir(&target)?;
+                    let result = unpack_partitions(binary, &staging, &target, verbose, depth - 1);
+                    fs::remove_dir_all(&staging)?;
+                    result?;
+                    fs::remove_file(&path)?;
+                    continue;
+                }
+                // 7z failing on it means it is not a filesystem it
+                // knows, the image is kept as it is
+                Err(Error::BackendFailed { .. }) => {
+                    let _ = fs::remove_dir_all(&staging);
+                }
+                Err(error) => {
+                    let _ = fs::remove_dir_all(&staging);
+                    return Err(error);
+                }
+            }
+        }
+
+        let target = to.join(name);
+        refuse_existing(&target)?;
+        fs::rename(&path, target)?;
+    }
+    Ok(())
+}
+
+/// Partitions and images sharing a name would otherwise replace each other.
+fn refuse_existing(path: &Path) -> Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(_) => Err(Error::OutputExists {
+            path: path.to_path_buf(),
+        }),
+        Err(_) => Ok(()),
+    }
+}
+
+fn is_partition(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| PARTITION_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// `1.fat` becomes `1`, `0.EFI System Partition.fat` becomes
+/// `0.EFI System Partition`.
+fn partition_dir(path: &Path, parent: &Path) -> PathBuf {
+    parent.join(path.file_stem().unwrap())
+}
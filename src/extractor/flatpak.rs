@@ -0,0 +1,81 @@
+use super::pkg::require_binary;
+use super::{Extractor, FileFormat};
+use crate::error::{Error, Result};
+use crate::trust::{capture_pipeline, run_pipeline, Pipeline};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Flatpak single-file bundles. The bundle is imported into a temporary
+/// OSTree repository inside the output directory, which is then checked
+/// out and removed.
+pub struct FlatpakExtractor;
+
+const REPO_DIR: &str = ".extr-flatpak-repo";
+
+fn repo_dir(output_dir: &Path) -> PathBuf {
+    output_dir.join(REPO_DIR)
+}
+
+impl Extractor for FlatpakExtractor {
+    fn file_extensions(&self) -> Vec<&'static str> {
+        vec!["flatpak"]
+    }
+
+    fn binary_names(&self) -> Vec<&'static str> {
+        vec!["flatpak"]
+    }
+
+    fn helper_binary_names(&self) -> Vec<&'static str> {
+        vec!["ostree"]
+    }
+
+    fn build_command(
+        &self,
+        binary: &Path,
+        file: &Path,
+        _format: &FileFormat,
+        output_dir: &Path,
+        _verbose: bool,
+    ) -> Result<Command> {
+        let mut cmd = Command::new(binary);
+        cmd.arg("build-import-bundle")
+            .arg("--no-update-summary")
+            .arg(repo_dir(output_dir))
+            .arg(file);
+        Ok(cmd)
+    }
+
+    fn after_extract(&self, file: &Path, output_dir: &Path, verbose: bool) -> Result<()> {
+        let repo = repo_dir(output_dir);
+        let checkout = || -> Result<()> {
+            let ostree = require_binary("flatpak", &["ostree"])?;
+
+            let mut refs = Command::new(&ostree);
+            refs.arg("refs").arg(format!("--repo={}", repo.display()));
+            let refs = capture_pipeline(Pipeline::new(refs))?;
+            let bundle_ref = String::from_utf8_lossy(&refs)
+                .lines()
+                .map(str::trim)
+                .find(|line| !line.is_empty())
+                .map(str::to_string)
+                .ok_or_else(|| Error::UnsupportedFormat {
+                    file: file.to_path_buf(),
+                })?;
+
+            let mut cmd = Command::new(&ostree);
+            cmd.arg("checkout")
+                .arg(format!("--repo={}", repo.display()))
+                .arg("--user-mode")
+                .arg("--union")
+                .arg(bundle_ref)
+                .arg(output_dir);
+            run_pipeline(Pipeline::new(cmd), verbose)
+        };
+
+        let result = checkout();
+        // A failed checkout is what went wrong, not the cleanup after it
+        let removed = std::fs::remove_dir_all(&repo);
+        result?;
+        Ok(removed?)
+    }
+}
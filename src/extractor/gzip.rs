@@ -1,5 +1,5 @@
-use super::Extractor;
-use anyhow::Result;
+use super::{Extractor, FileFormat};
+use crate::error::Result;
 use std::path::Path;
 use std::process::Command;
 
@@ -7,35 +7,38 @@ pub struct GzipExtractor;
 
 impl Extractor for GzipExtractor {
     fn file_extensions(&self) -> Vec<&'static str> {
-        vec!["gz"]
+        // Compressed SVG and Windows metafiles are plain gzip streams
+        vec!["gz", "svgz", "emz", "wmz"]
     }
 
     fn binary_names(&self) -> Vec<&'static str> {
         vec!["gzip", "gunzip"]
     }
 
+    fn is_single_stream(&self) -> bool {
+        true
+    }
+
     fn build_command(
         &self,
         binary: &Path,
         file: &Path,
-        output_dir: &Path,
+        _format: &FileFormat,
+        _output_dir: &Path,
         verbose: bool,
     ) -> Result<Command> {
         let mut cmd = Command::new(binary);
 
-        std::fs::create_dir_all(output_dir)?;
-
         if binary.ends_with("gzip") {
             cmd.arg("-d");
         }
 
-        cmd.arg("-k");
+        cmd.arg("-c");
 
         if verbose {
             cmd.arg("-v");
         }
 
-        cmd.current_dir(output_dir);
         cmd.arg(file);
 
         Ok(cmd)
@@ -0,0 +1,58 @@
+use super::pkg::{find_member, is_ar, member_command, tar_from_stdin};
+use super::{Extractor, FileFormat};
+use crate::error::Result;
+use crate::package;
+use crate::trust::Pipeline;
+use std::path::Path;
+use std::process::Command;
+
+/// OpenWrt packages, which like `.deb` hold a `control.tar.gz` and a
+/// `data.tar.gz`, either in an `ar` archive or in a gzipped tarball.
+pub struct IpkExtractor;
+
+impl Extractor for IpkExtractor {
+    fn file_extensions(&self) -> Vec<&'static str> {
+        vec!["ipk"]
+    }
+
+    fn binary_names(&self) -> Vec<&'static str> {
+        vec!["bsdtar", "tar", "ar"]
+    }
+
+    fn build_command(
+        &self,
+        binary: &Path,
+        file: &Path,
+        _format: &FileFormat,
+        _output_dir: &Path,
+        _verbose: bool,
+    ) -> Result<Command> {
+        let bin_name = binary.file_name().unwrap().to_str().unwrap();
+
+        match (bin_name, is_ar(file)?) {
+            ("bsdtar", _) | ("ar", true) | ("tar", false) => {
+                let member = find_member(file, "data.tar")?;
+                Ok(member_command(binary, file, &member))
+            }
+            _ => Err(self.unsupported_backend(bin_name)),
+        }
+    }
+
+    fn build_pipeline(
+        &self,
+        binary: &Path,
+        file: &Path,
+        format: &FileFormat,
+        output_dir: &Path,
+        verbose: bool,
+    ) -> Result<Pipeline> {
+        let cmd = self.build_command(binary, file, format, output_dir, verbose)?;
+        let member = find_member(file, "data.tar")?;
+
+        Ok(Pipeline::new(cmd).pipe(tar_from_stdin(&member, output_dir, verbose)?))
+    }
+
+    fn after_extract(&self, file: &Path, output_dir: &Path, verbose: bool) -> Result<()> {
+        package::write_control_meta(file, output_dir, verbose)
+    }
+}
@@ -1,5 +1,5 @@
-use super::Extractor;
-use anyhow::Result;
+use super::{Extractor, FileFormat};
+use crate::error::Result;
 use std::path::Path;
 use std::process::Command;
 
@@ -18,12 +18,14 @@ impl Extractor for LhaExtractor {
         &self,
         binary: &Path,
         file: &Path,
+        _format: &FileFormat,
         output_dir: &Path,
         verbose: bool,
     ) -> Result<Command> {
         let mut cmd = Command::new(binary);
+        let bin_name = binary.file_name().unwrap().to_str().unwrap();
 
-        match binary.file_name().unwrap().to_str().unwrap() {
+        match bin_name {
             "lha" | "lhasa" => {
                 cmd.arg("x");
                 if verbose {
@@ -46,7 +48,7 @@ impl Extractor for LhaExtractor {
                     cmd.arg("-bso0").arg("-bd");
                 }
             }
-            _ => anyhow::bail!("Unsupported LHA/LZH extraction tool: {}", binary.display()),
+            _ => return Err(self.unsupported_backend(bin_name)),
         }
 
         Ok(cmd)
@@ -0,0 +1,42 @@
+use super::{Extractor, FileFormat};
+use crate::error::Result;
+use std::path::Path;
+use std::process::Command;
+
+pub struct Lz4Extractor;
+
+impl Extractor for Lz4Extractor {
+    fn file_extensions(&self) -> Vec<&'static str> {
+        vec!["lz4"]
+    }
+
+    fn binary_names(&self) -> Vec<&'static str> {
+        vec!["lz4"]
+    }
+
+    fn is_single_stream(&self) -> bool {
+        true
+    }
+
+    fn build_command(
+        &self,
+        binary: &Path,
+        file: &Path,
+        _format: &FileFormat,
+        _output_dir: &Path,
+        verbose: bool,
+    ) -> Result<Command> {
+        let mut cmd = Command::new(binary);
+
+        cmd.arg("-d");
+        cmd.arg("-c");
+
+        if verbose {
+            cmd.arg("-v");
+        }
+
+        cmd.arg(file);
+
+        Ok(cmd)
+    }
+}
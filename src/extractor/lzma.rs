@@ -1,5 +1,5 @@
-use super::Extractor;
-use anyhow::Result;
+use super::{Extractor, FileFormat};
+use crate::error::Result;
 use std::path::Path;
 use std::process::Command;
 
@@ -14,28 +14,30 @@ impl Extractor for LzmaExtractor {
         vec!["lzma", "unlzma"]
     }
 
+    fn is_single_stream(&self) -> bool {
+        true
+    }
+
     fn build_command(
         &self,
         binary: &Path,
         file: &Path,
-        output_dir: &Path,
+        _format: &FileFormat,
+        _output_dir: &Path,
         verbose: bool,
     ) -> Result<Command> {
         let mut cmd = Command::new(binary);
 
-        std::fs::create_dir_all(output_dir)?;
-
         if binary.ends_with("lzma") {
             cmd.arg("-d");
         }
 
-        cmd.arg("-k");
+        cmd.arg("-c");
 
         if verbose {
             cmd.arg("-v");
         }
 
-        cmd.current_dir(output_dir);
         cmd.arg(file);
 
         Ok(cmd)
@@ -1,37 +1,65 @@
-use anyhow::{Context, Result};
 use lazy_static::lazy_static;
 use std::collections::{BTreeMap, HashMap};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::sync::RwLock;
 
+use crate::error::{Error, FailureReason, Result};
+use crate::extractor::apk::ApkExtractor;
+use crate::extractor::appimage::AppImageExtractor;
+use crate::extractor::archpkg::ArchPkgExtractor;
 use crate::extractor::arj::ArjExtractor;
+use crate::extractor::brotli::BrotliExtractor;
 use crate::extractor::bzip2::Bzip2Extractor;
 use crate::extractor::cab::CabExtractor;
 use crate::extractor::compress::CompressExtractor;
+use crate::extractor::compression::{decoder_pipeline, Compression};
+use crate::extractor::cpio::CpioExtractor;
+use crate::extractor::disk::DiskImageExtractor;
+use crate::extractor::flatpak::FlatpakExtractor;
 use crate::extractor::gzip::GzipExtractor;
+use crate::extractor::ipk::IpkExtractor;
 use crate::extractor::iso::IsoExtractor;
 use crate::extractor::lha::LhaExtractor;
+use crate::extractor::lz4::Lz4Extractor;
 use crate::extractor::lzip::LzipExtractor;
 use crate::extractor::lzma::LzmaExtractor;
 use crate::extractor::lzop::LzopExtractor;
-use crate::extractor::pkg::DebRpmExtractor;
+use crate::extractor::pkg::{DebExtractor, RpmExtractor};
 use crate::extractor::rar::RarExtractor;
 use crate::extractor::sevenz::SevenzExtractor;
 use crate::extractor::sfx::SfxExtractor;
+use crate::extractor::snap::SnapExtractor;
+use crate::extractor::snappy::SnappyExtractor;
+use crate::extractor::squashfs::SquashfsExtractor;
 use crate::extractor::tar::TarExtractor;
+use crate::extractor::wim::WimExtractor;
 use crate::extractor::xz::XzExtractor;
 use crate::extractor::zip::ZipExtractor;
 use crate::extractor::zstd::ZstdExtractor;
-use crate::trust::{is_trusted_bin_path, run_command};
+use crate::output::{merge_staging, remove_path};
+use crate::preserve::Preservation;
+use crate::probe::{format_features, probe_tool, Feature};
+use crate::trust::{find_trusted_binary, run_pipeline, Pipeline};
 
+pub mod apk;
+pub mod appimage;
+pub mod archpkg;
 pub mod arj;
+pub mod brotli;
 pub mod bzip2;
 pub mod cab;
 pub mod compress;
+pub mod compression;
+pub mod cpio;
+pub mod custom;
+pub mod disk;
+pub mod flatpak;
 pub mod gzip;
+pub mod ipk;
 pub mod iso;
 pub mod lha;
+pub mod lz4;
 pub mod lzip;
 pub mod lzma;
 pub mod lzop;
@@ -39,99 +67,555 @@ pub mod pkg;
 pub mod rar;
 pub mod sevenz;
 pub mod sfx;
+pub mod snap;
+pub mod snappy;
+pub mod squashfs;
+pub mod stream;
 pub mod tar;
+pub mod wim;
 pub mod xz;
 pub mod zip;
 pub mod zstd;
 
-pub fn register_extractors() {
-    register_extractor(&ArjExtractor);
-    register_extractor(&Bzip2Extractor);
-    register_extractor(&CabExtractor);
-    register_extractor(&CompressExtractor);
-    register_extractor(&GzipExtractor);
-    register_extractor(&IsoExtractor);
-    register_extractor(&LhaExtractor);
-    register_extractor(&LzipExtractor);
-    register_extractor(&LzmaExtractor);
-    register_extractor(&LzopExtractor);
-    register_extractor(&DebRpmExtractor);
-    register_extractor(&RarExtractor);
-    register_extractor(&SevenzExtractor);
-    register_extractor(&SfxExtractor);
-    register_extractor(&TarExtractor);
-    register_extractor(&XzExtractor);
-    register_extractor(&ZipExtractor);
-    register_extractor(&ZstdExtractor);
-}
+const BUILTIN_EXTRACTORS: &[&dyn Extractor] = &[
+    &ApkExtractor,
+    &AppImageExtractor,
+    &ArchPkgExtractor,
+    &ArjExtractor,
+    &BrotliExtractor,
+    &Bzip2Extractor,
+    &CabExtractor,
+    &CompressExtractor,
+    &CpioExtractor,
+    &DiskImageExtractor,
+    &FlatpakExtractor,
+    &GzipExtractor,
+    &IpkExtractor,
+    &IsoExtractor,
+    &LhaExtractor,
+    &Lz4Extractor,
+    &LzipExtractor,
+    &LzmaExtractor,
+    &LzopExtractor,
+    &DebExtractor,
+    &RpmExtractor,
+    &RarExtractor,
+    &SevenzExtractor,
+    &SfxExtractor,
+    &SnapExtractor,
+    &SnappyExtractor,
+    &SquashfsExtractor,
+    &TarExtractor,
+    &WimExtractor,
+    &XzExtractor,
+    &ZipExtractor,
+    &ZstdExtractor,
+];
 
+/// Implemented by every supported archive format. Built-in formats are
+/// registered automatically, additional ones can be added with
+/// [`register_extractor`].
 pub trait Extractor: Send + Sync {
     fn file_extensions(&self) -> Vec<&str>;
 
     fn binary_names(&self) -> Vec<&'static str>;
 
-    fn get_verified_binary(&self) -> Result<std::path::PathBuf> {
-        self.binary_names()
-            .into_iter()
-            .find_map(|binary| {
-                if let Ok(path) = which::which(binary) {
-                    if is_trusted_bin_path(&path) {
-                        return Some(path);
-                    }
-                    None
-                } else {
-                    None
-                }
-            })
-            .context(format!(
-                "💥 Whoops! No suitable extraction tool found on your system for {} archives. Please use your package manager to install one of: {}",
-                self.file_extensions().join(", "),
-                self.binary_names().join(", ")
-            ))
+    /// Tools that have to be installed next to whichever of
+    /// [`binary_names`](Extractor::binary_names) is used, like `ostree` for
+    /// checking out flatpak bundles.
+    fn helper_binary_names(&self) -> Vec<&'static str> {
+        Vec::new()
+    }
+
+    fn get_verified_binaries(&self, backends: &[&'static str]) -> Vec<PathBuf> {
+        if self
+            .helper_binary_names()
+            .iter()
+            .any(|helper| find_trusted_binary(helper).is_none())
+        {
+            return Vec::new();
+        }
+
+        backends
+            .iter()
+            .filter_map(|binary| find_trusted_binary(binary))
+            .collect()
+    }
+
+    /// Returns the first installed tool in `backends`. When a tool is only
+    /// found outside the trusted system directories, that is reported
+    /// instead of the tool being missing.
+    fn get_verified_binary(&self, backends: &[&'static str]) -> Result<PathBuf> {
+        for helper in self.helper_binary_names() {
+            pkg::require_binary(self.file_extensions()[0], &[helper])?;
+        }
+
+        if let Some(binary) = self.get_verified_binaries(backends).into_iter().next() {
+            return Ok(binary);
+        }
+
+        if let Some((tool, path)) = backends
+            .iter()
+            .find_map(|tool| which::which(tool).ok().map(|path| (tool, path)))
+        {
+            return Err(Error::UntrustedTool {
+                tool: tool.to_string(),
+                path,
+            });
+        }
+
+        Err(Error::NoToolInstalled {
+            format: self.file_extensions()[0].to_string(),
+            tools: backends.iter().map(|tool| tool.to_string()).collect(),
+        })
+    }
+
+    /// The error for a `tool` this extractor does not know how to drive.
+    fn unsupported_backend(&self, tool: &str) -> Error {
+        Error::UnsupportedBackend {
+            tool: tool.to_string(),
+            format: self.file_extensions()[0].to_string(),
+            supported: self
+                .binary_names()
+                .into_iter()
+                .map(str::to_string)
+                .collect(),
+        }
     }
 
     fn build_command(
         &self,
         binary: &Path,
         file: &Path,
+        format: &FileFormat,
         output_dir: &Path,
         verbose: bool,
     ) -> Result<Command>;
 
-    fn extract(&self, file: &Path, output_dir: &Path, verbose: bool) -> Result<()> {
-        let binary = self.get_verified_binary()?;
-        let cmd = self.build_command(&binary, file, output_dir, verbose)?;
+    /// Formats that need more than one tool chained together override this
+    /// instead of [`Extractor::build_command`].
+    fn build_pipeline(
+        &self,
+        binary: &Path,
+        file: &Path,
+        format: &FileFormat,
+        output_dir: &Path,
+        verbose: bool,
+    ) -> Result<Pipeline> {
+        self.build_command(binary, file, format, output_dir, verbose)
+            .map(Pipeline::from)
+    }
+
+    /// Formats stored as several archives one after another, such as an
+    /// initramfs, override this to extract each of them in turn.
+    fn build_pipelines(
+        &self,
+        binary: &Path,
+        file: &Path,
+        format: &FileFormat,
+        output_dir: &Path,
+        verbose: bool,
+    ) -> Result<Vec<Pipeline>> {
+        Ok(vec![self.build_pipeline(
+            binary, file, format, output_dir, verbose,
+        )?])
+    }
+
+    /// Tools in [`Extractor::binary_names`] able to read the archive from
+    /// stdin. Formats with any of them can be wrapped in any compression,
+    /// such as `.cpio.zst` or a base64 encoded `.tar.gz`.
+    fn stdin_binary_names(&self) -> Vec<&'static str> {
+        Vec::new()
+    }
+
+    /// The command unpacking the archive read from stdin with `binary`, one
+    /// of [`Extractor::stdin_binary_names`].
+    fn build_stdin_command(
+        &self,
+        binary: &Path,
+        _format: &FileFormat,
+        _output_dir: &Path,
+        _verbose: bool,
+    ) -> Result<Command> {
+        Err(self.unsupported_backend(&binary.file_name().unwrap_or_default().to_string_lossy()))
+    }
+
+    /// Recognises the format from `header`, the first bytes of a file whose
+    /// name does not tell what it is.
+    fn matches_header(&self, _header: &[u8]) -> bool {
+        false
+    }
+
+    /// Single-stream formats such as `.gz` hold one compressed file rather
+    /// than an archive. Their command writes the decompressed data to
+    /// stdout, which ends up in a file named after the archive in
+    /// `output_dir`.
+    fn is_single_stream(&self) -> bool {
+        false
+    }
+
+    /// Runs once the tool has extracted `file` successfully, for formats
+    /// that write more than the tool itself does.
+    fn after_extract(&self, _file: &Path, _output_dir: &Path, _verbose: bool) -> Result<()> {
+        Ok(())
+    }
+
+    /// Everything run to extract `file` into `output_dir` with `binary`,
+    /// one pipeline after another, with the options for `preservation` the
+    /// tool has. Nothing is run or written.
+    fn build_extraction(
+        &self,
+        binary: &Path,
+        file: &Path,
+        format: &FileFormat,
+        output_dir: &Path,
+        verbose: bool,
+        preservation: &Preservation,
+    ) -> Result<Vec<Pipeline>> {
+        let pipelines = if format.is_layered() {
+            let decoders = decoder_pipeline(&format.compression, file)?;
+            let cmd = self.build_stdin_command(binary, format, output_dir, verbose)?;
+            vec![decoders.pipe(cmd)]
+        } else {
+            self.build_pipelines(binary, file, format, output_dir, verbose)?
+        };
+
+        Ok(pipelines
+            .into_iter()
+            .map(|pipeline| {
+                let tool = pipeline
+                    .commands()
+                    .last()
+                    .and_then(|cmd| Path::new(cmd.get_program()).file_name())
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                pipeline.prepend_args(&preservation.tool_args(&tool))
+            })
+            .collect())
+    }
+
+    /// Extracts `file` with the first installed tool in `backends` and
+    /// returns the path of the tool that did the work. With `fallback` set,
+    /// a failing tool is followed by the next installed one. Every tool then
+    /// extracts into a directory of its own in `output_dir`, which is
+    /// removed when it fails and merged into `output_dir` when it works, and
+    /// `on_retry` is told about the error and the tool taking over. The
+    /// tool is given the options for `preservation` it has.
+    #[allow(clippy::too_many_arguments)]
+    fn extract(
+        &self,
+        file: &Path,
+        format: &FileFormat,
+        output_dir: &Path,
+        backends: &[&'static str],
+        fallback: bool,
+        verbose: bool,
+        preservation: &Preservation,
+        on_retry: &dyn Fn(&Error, &Path),
+    ) -> Result<PathBuf> {
+        let binaries = if fallback {
+            self.get_verified_binaries(backends)
+        } else {
+            vec![self.get_verified_binary(backends)?]
+        };
+
         std::fs::create_dir_all(output_dir)?;
-        run_command(cmd, verbose)
+
+        let stream_target = if self.is_single_stream() {
+            let target = stream::output_path(file, output_dir, &self.file_extensions());
+            stream::check_output(&target)?;
+            Some(target)
+        } else {
+            None
+        };
+
+        // With another tool to take over, each one writes into a directory
+        // of its own, so a failure leaves nothing behind for the next
+        let staged = stream_target.is_none() && binaries.len() > 1;
+        let name = file.file_name().unwrap_or_default().to_string_lossy();
+
+        let mut last_error = None;
+        for (attempt, binary) in binaries.iter().enumerate() {
+            if let Some(error) = last_error.take() {
+                on_retry(&error, binary);
+            }
+
+            let into = if staged {
+                output_dir.join(format!(".{name}.extr-attempt{attempt}"))
+            } else {
+                output_dir.to_path_buf()
+            };
+
+            let result = std::fs::create_dir_all(&into)
+                .map_err(Error::from)
+                .and_then(|()| {
+                    self.build_extraction(binary, file, format, &into, verbose, preservation)
+                })
+                .and_then(|pipelines| {
+                    pipelines
+                        .into_iter()
+                        .try_for_each(|pipeline| match &stream_target {
+                            Some(target) => stream::run_stream(pipeline, file, target, verbose),
+                            None => run_pipeline(pipeline, verbose),
+                        })
+                })
+                .map_err(|error| diagnose(error, file));
+
+            match result {
+                Ok(()) => {
+                    let placed = self.after_extract(file, &into, verbose).and_then(|()| {
+                        if staged {
+                            merge_staging(&into, file, output_dir)?;
+                        }
+                        Ok(())
+                    });
+                    if staged && into.exists() {
+                        remove_path(&into)?;
+                    }
+                    placed?;
+                    return Ok(binary.clone());
+                }
+                Err(error) => {
+                    if staged && into.exists() {
+                        remove_path(&into)?;
+                    }
+                    if matches!(error, Error::Cancelled { .. }) {
+                        return Err(error);
+                    }
+                    last_error = Some(error);
+                }
+            }
+        }
+
+        match last_error {
+            Some(error) => Err(error),
+            None => self.get_verified_binary(backends),
+        }
+    }
+}
+
+/// Turns a failed tool run into a recognised archive problem when the
+/// tool's error output says what went wrong.
+fn diagnose(error: Error, file: &Path) -> Error {
+    match error {
+        Error::BackendFailed { tool, code, stderr } => match FailureReason::from_stderr(&stderr) {
+            Some(reason) => Error::ArchiveFailed {
+                file: file.to_path_buf(),
+                tool,
+                reason,
+            },
+            None => Error::BackendFailed { tool, code, stderr },
+        },
+        error => error,
     }
 }
 
 lazy_static! {
-    static ref EXTRACTORS: RwLock<HashMap<&'static str, &'static dyn Extractor>> =
-        RwLock::new(HashMap::new());
+    static ref EXTRACTORS: RwLock<HashMap<&'static str, &'static dyn Extractor>> = {
+        let mut extractors = HashMap::new();
+        for extractor in BUILTIN_EXTRACTORS {
+            for ext in extractor.file_extensions() {
+                extractors.insert(ext, *extractor);
+            }
+        }
+        RwLock::new(extractors)
+    };
+}
+
+/// Decides which tools are tried, and in which order, for each format.
+#[derive(Debug, Default, Clone)]
+pub struct BackendSelection {
+    forced: Option<String>,
+    preferred: BTreeMap<String, Vec<String>>,
 }
 
-pub fn get_health_info() -> Vec<(String, Vec<(String, bool)>)> {
+impl BackendSelection {
+    /// `forced` is the single tool requested with `--backend`, `preferred`
+    /// maps formats to ordered lists of tools that are tried before the
+    /// built-in order. Formats are matched case-insensitively.
+    pub fn new(forced: Option<String>, preferred: BTreeMap<String, Vec<String>>) -> Result<Self> {
+        let extractors = EXTRACTORS.read().unwrap();
+        let preferred: BTreeMap<String, Vec<String>> = preferred
+            .into_iter()
+            .map(|(format, tools)| (format.to_lowercase(), tools))
+            .collect();
+
+        for (format, tools) in &preferred {
+            let extractor = extractors
+                .get(format.as_str())
+                .ok_or_else(|| Error::Config {
+                    path: None,
+                    message: format!("unknown format .{format} in backends"),
+                })?;
+            for tool in tools {
+                check_supported(format, *extractor, tool)?;
+            }
+        }
+
+        Ok(Self { forced, preferred })
+    }
+
+    /// Tools to try for an identified file. Layered formats only get the
+    /// tools that can read the archive from stdin.
+    pub fn candidates_for(
+        &self,
+        format: &FileFormat,
+        extractor: &dyn Extractor,
+    ) -> Result<Vec<&'static str>> {
+        if !format.is_layered() {
+            return self.candidates(&format.extension, extractor);
+        }
+
+        let streaming = extractor.stdin_binary_names();
+        if let Some(forced) = self.forced.as_deref() {
+            if !streaming.contains(&forced) {
+                return Err(Error::UnsupportedBackend {
+                    tool: forced.to_string(),
+                    format: format.extension.clone(),
+                    supported: streaming.iter().map(|tool| tool.to_string()).collect(),
+                });
+            }
+        }
+
+        Ok(self
+            .candidates(format.registry_key(), extractor)?
+            .into_iter()
+            .filter(|tool| streaming.contains(tool))
+            .collect())
+    }
+
+    /// Tools to try for `format`, most preferred first.
+    pub fn candidates(&self, format: &str, extractor: &dyn Extractor) -> Result<Vec<&'static str>> {
+        let supported = extractor.binary_names();
+
+        if let Some(forced) = &self.forced {
+            let tool = check_supported(format, extractor, forced)?;
+            return Ok(vec![tool]);
+        }
+
+        let mut candidates = Vec::with_capacity(supported.len());
+        if let Some(preferred) = self.preferred.get(format) {
+            for tool in preferred {
+                let tool = check_supported(format, extractor, tool)?;
+                if !candidates.contains(&tool) {
+                    candidates.push(tool);
+                }
+            }
+        }
+        for tool in supported {
+            if !candidates.contains(&tool) {
+                candidates.push(tool);
+            }
+        }
+
+        Ok(candidates)
+    }
+}
+
+fn check_supported(format: &str, extractor: &dyn Extractor, tool: &str) -> Result<&'static str> {
+    let supported = extractor.binary_names();
+    supported
+        .iter()
+        .find(|name| **name == tool)
+        .copied()
+        .ok_or_else(|| Error::UnsupportedBackend {
+            tool: tool.to_string(),
+            format: format.to_string(),
+            supported: supported.iter().map(|tool| tool.to_string()).collect(),
+        })
+}
+
+/// Which tools are installed for a format, as reported by `extr --health`.
+pub struct FormatHealth {
+    pub format: String,
+    /// Every supported tool, followed by the helper tools the format needs
+    /// as well, and whether it is installed and able to open the format
+    pub binaries: Vec<(String, bool)>,
+    /// The tool that would be used to extract this format, if any
+    pub selected: Option<String>,
+    /// Versions of the installed tools, by tool name
+    pub versions: BTreeMap<String, String>,
+    /// Optional features of the format, with the first usable tool that
+    /// handles each of them
+    pub features: Vec<(Feature, Option<String>)>,
+}
+
+/// Collects tool availability for every registered format, sorted by format.
+/// Installed tools are run once to find out their versions and abilities.
+pub fn get_health_info(selection: &BackendSelection) -> Vec<FormatHealth> {
     let extractors = EXTRACTORS.read().unwrap();
     let mut formats = BTreeMap::new();
 
     for (ext, extractor) in extractors.iter() {
-        let binaries = extractor
+        let tools = extractor
             .binary_names()
             .into_iter()
-            .map(|bin| {
-                let exists = which::which(bin)
-                    .map(|path| is_trusted_bin_path(&path))
-                    .unwrap_or(false);
-                (bin.to_string(), exists)
+            .map(|bin| (bin, probe_tool(bin).filter(|tool| tool.opens(ext))))
+            .collect::<Vec<_>>();
+        let helpers = extractor
+            .helper_binary_names()
+            .into_iter()
+            .map(|bin| (bin, probe_tool(bin)))
+            .collect::<Vec<_>>();
+        let helpers_installed = helpers.iter().all(|(_, tool)| tool.is_some());
+
+        let binaries = tools
+            .iter()
+            .chain(&helpers)
+            .map(|(bin, tool)| (bin.to_string(), tool.is_some()))
+            .collect::<Vec<_>>();
+
+        let versions = tools
+            .iter()
+            .chain(&helpers)
+            .filter_map(|(bin, tool)| {
+                let version = tool.as_ref()?.version.clone()?;
+                Some((bin.to_string(), version))
+            })
+            .collect();
+
+        let candidates = selection.candidates(ext, *extractor).unwrap_or_default();
+        let usable = candidates
+            .iter()
+            .filter(|_| helpers_installed)
+            .filter_map(|candidate| {
+                tools
+                    .iter()
+                    .find(|(bin, _)| bin == candidate)
+                    .and_then(|(_, tool)| tool.as_ref())
+            })
+            .collect::<Vec<_>>();
+
+        let selected = usable.first().map(|tool| tool.name.clone());
+
+        let features = format_features(ext)
+            .iter()
+            .map(|feature| {
+                let tool = usable
+                    .iter()
+                    .find(|tool| tool.supports(*feature))
+                    .map(|tool| tool.name.clone());
+                (*feature, tool)
             })
             .collect();
-        formats.insert(ext.to_string(), binaries);
+
+        formats.insert(
+            ext.to_string(),
+            FormatHealth {
+                format: ext.to_string(),
+                binaries,
+                selected,
+                versions,
+                features,
+            },
+        );
     }
 
-    formats.into_iter().collect()
+    formats.into_values().collect()
 }
 
+/// Adds `extractor` to the registry, replacing any earlier extractor for the
+/// same file extensions.
 pub fn register_extractor(extractor: &'static dyn Extractor) {
     let mut extractors = EXTRACTORS.write().unwrap();
     for ext in extractor.file_extensions() {
@@ -139,19 +623,241 @@ pub fn register_extractor(extractor: &'static dyn Extractor) {
     }
 }
 
+/// Finds the extractor for `path` based on its file extension. For files
+/// wrapped in layers of compression that is the extractor of the innermost
+/// archive.
 pub fn get_extractor(path: &Path) -> Option<&'static dyn Extractor> {
-    let extension = get_extension(path)?;
+    get_format_extractor(&identify(path)?)
+}
+
+/// Finds the extractor registered for an identified format.
+pub fn get_format_extractor(format: &FileFormat) -> Option<&'static dyn Extractor> {
     let extractors = EXTRACTORS.read().unwrap();
-    extractors.get(extension.as_str()).copied()
+    extractors.get(format.registry_key()).copied()
+}
+
+/// Identifies `path` by its first bytes, for files whose name does not tell
+/// the format. Only extractors that know the magic bytes of their format,
+/// such as custom ones from the configuration, are matched.
+pub fn identify_contents(path: &Path) -> Option<FileFormat> {
+    use std::io::Read;
+
+    let mut header = Vec::with_capacity(custom::MAX_MAGIC_END);
+    std::fs::File::open(path)
+        .ok()?
+        .take(custom::MAX_MAGIC_END as u64)
+        .read_to_end(&mut header)
+        .ok()?;
+
+    let extractors = EXTRACTORS.read().unwrap();
+    let mut matching = extractors
+        .iter()
+        .filter(|(_, extractor)| extractor.matches_header(&header))
+        .map(|(ext, _)| *ext)
+        .collect::<Vec<_>>();
+    matching.sort_unstable();
+
+    matching.first().map(|ext| FileFormat {
+        extension: ext.to_string(),
+        container: None,
+        compression: Vec::new(),
+    })
+}
+
+/// Tarball shorthands that stand for a compressed `tar`.
+const TARBALL_SHORTHANDS: &[&str] = &["tgz", "taz", "tbz", "tbz2", "tb2", "txz", "tlz", "tzst"];
+
+/// What a file is, worked out once from its name and handed to every
+/// extractor so that none of them has to look at the path again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileFormat {
+    /// Lowercased format identifier, e.g. `zip`, `tar.gz`, `tgz` or
+    /// `cpio.xz`
+    pub extension: String,
+    /// Registry key of the archive inside the layers, set when the file is
+    /// only known as a stack of layers, e.g. `cpio` for `initrd.cpio.xz`
+    pub container: Option<String>,
+    /// Compression wrapping the contents, outermost first. Empty for
+    /// uncompressed containers such as `tar` and formats with their own
+    /// built-in compression such as `zip`.
+    pub compression: Vec<Compression>,
+}
+
+impl FileFormat {
+    /// The key the extractor for this format is registered under.
+    pub fn registry_key(&self) -> &str {
+        self.container.as_deref().unwrap_or(&self.extension)
+    }
+
+    /// Whether extr decodes the layers itself and feeds the innermost
+    /// archive to the tool on stdin.
+    pub fn is_layered(&self) -> bool {
+        self.container.is_some()
+    }
 }
 
-fn get_extension(path: &Path) -> Option<String> {
-    let file_name = path.file_name()?.to_str()?;
+/// Identifies `path` by its file name, case-insensitively, keeping `tar.`
+/// compound extensions such as `tar.gz` together. Boot images named like
+/// `initrd.img-6.1.0` or `initramfs-linux.img` are `initramfs`.
+///
+/// Names that are not registered as a whole, or that would only be
+/// decompressed into a single file, are read as layers around an archive
+/// instead: `image.cpio.zst` is a `cpio` archive in a `zstd` layer and
+/// `backup.tar.gz.b64` a `tar` archive in `base64` and `gzip` layers.
+pub fn identify(path: &Path) -> Option<FileFormat> {
+    let file_name = path.file_name()?.to_str()?.to_lowercase();
+
+    if is_initramfs_name(&file_name) {
+        return Some(FileFormat {
+            extension: "initramfs".to_string(),
+            container: None,
+            compression: Vec::new(),
+        });
+    }
+
     let parts: Vec<&str> = file_name.split('.').collect();
+    let (extension, compressed) = match parts.as_slice() {
+        [.., "pkg", "tar", last] => (format!("pkg.tar.{last}"), *last),
+        [.., "pkg", "tar"] => ("pkg.tar".to_string(), "tar"),
+        [.., "tar", last] => (format!("tar.{last}"), *last),
+        [.., last] => (last.to_string(), *last),
+        _ => return None,
+    };
+
+    let extractors = EXTRACTORS.read().unwrap();
+    let whole = extractors.get(extension.as_str());
+    if whole.map_or(true, |extractor| extractor.is_single_stream()) {
+        if let Some(layered) = identify_layers(&parts, &extractors) {
+            return Some(layered);
+        }
+    }
+
+    Some(FileFormat {
+        extension,
+        container: None,
+        compression: Compression::from_extension(compressed)
+            .into_iter()
+            .collect(),
+    })
+}
+
+/// Peels compression extensions off the end of `parts` and returns the
+/// layers if what remains is an archive that can be read from stdin.
+fn identify_layers(
+    parts: &[&str],
+    extractors: &HashMap<&'static str, &'static dyn Extractor>,
+) -> Option<FileFormat> {
+    let mut compression = Vec::new();
+    let mut end = parts.len();
+    let mut container = None;
+
+    while end > 1 {
+        let part = parts[end - 1];
+        let layer = Compression::from_extension(part)?;
+        compression.push(layer);
+        end -= 1;
+
+        if TARBALL_SHORTHANDS.contains(&part) {
+            container = Some("tar");
+            end += 1;
+            break;
+        }
+        if Compression::from_extension(parts[end - 1]).is_none() {
+            break;
+        }
+    }
+
+    let container = match container {
+        Some(container) => container,
+        None if end > 1 => parts[end - 1],
+        None => return None,
+    };
+
+    let extractor = extractors.get(container)?;
+    if compression.is_empty() || extractor.stdin_binary_names().is_empty() {
+        return None;
+    }
+
+    Some(FileFormat {
+        extension: parts[end - 1..].join("."),
+        container: Some(container.to_string()),
+        compression,
+    })
+}
+
+/// Returns the lowercased extension used to look up the extractor for
+/// `path`, see [`identify`].
+pub fn get_extension(path: &Path) -> Option<String> {
+    identify(path).map(|format| format.extension)
+}
+
+fn is_initramfs_name(name: &str) -> bool {
+    (name.starts_with("initrd") || name.starts_with("initramfs"))
+        && (name.ends_with(".img") || name.contains(".img-") || !name.contains('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identified(name: &str) -> (String, Option<String>, Vec<Compression>) {
+        let format = identify(Path::new(name)).unwrap();
+        (format.extension, format.container, format.compression)
+    }
+
+    fn layers(name: &str) -> Option<FileFormat> {
+        let parts = name.split('.').collect::<Vec<_>>();
+        identify_layers(&parts, &EXTRACTORS.read().unwrap())
+    }
+
+    #[test]
+    fn identifies_case_insensitively_and_keeps_tar_compounds() {
+        assert_eq!(
+            identified("dist/Release-1.0.TAR.GZ"),
+            ("tar.gz".to_string(), None, vec![Compression::Gzip])
+        );
+        assert_eq!(
+            identified("core-6.1-x86_64.Pkg.Tar.Zst"),
+            ("pkg.tar.zst".to_string(), None, vec![Compression::Zstd])
+        );
+        assert_eq!(identified("Photos.ZIP"), ("zip".to_string(), None, vec![]));
+        assert_eq!(
+            identified("backup.TGZ"),
+            ("tgz".to_string(), None, vec![Compression::Gzip])
+        );
+    }
+
+    #[test]
+    fn identifies_archives_inside_layers() {
+        assert_eq!(
+            identified("image.cpio.zst"),
+            (
+                "cpio.zst".to_string(),
+                Some("cpio".to_string()),
+                vec![Compression::Zstd]
+            )
+        );
+        assert_eq!(
+            identified("backup.tgz.b64"),
+            (
+                "tgz.b64".to_string(),
+                Some("tar".to_string()),
+                vec![Compression::Base64, Compression::Gzip]
+            )
+        );
+        assert_eq!(
+            identified("notes.txt.gz"),
+            ("gz".to_string(), None, vec![Compression::Gzip])
+        );
+    }
 
-    match parts.as_slice() {
-        [.., "tar", last] => Some(format!("{}.{}", "tar", last).to_lowercase()),
-        [.., last] => Some(last.to_lowercase()),
-        _ => None,
+    #[test]
+    fn layers_need_an_archive_underneath() {
+        assert_eq!(layers("notes.gz"), None);
+        assert_eq!(layers("image.cpio"), None);
+        assert_eq!(
+            layers("image.cpio.xz.gz").map(|format| format.compression),
+            Some(vec![Compression::Gzip, Compression::Xz])
+        );
     }
 }
@@ -1,23 +1,93 @@
-use super::Extractor;
-use anyhow::Result;
-use std::path::Path;
+use super::cpio::confine_args;
+use super::{Extractor, FileFormat};
+use crate::error::{Error, Result};
+use crate::package;
+use crate::trust::{capture_pipeline, find_trusted_binary, Pipeline};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
-pub struct DebRpmExtractor;
+pub struct DebExtractor;
 
-impl Extractor for DebRpmExtractor {
+impl Extractor for DebExtractor {
     fn file_extensions(&self) -> Vec<&'static str> {
-        vec!["deb", "rpm"]
+        vec!["deb"]
     }
 
     fn binary_names(&self) -> Vec<&'static str> {
-        vec!["bsdtar", "ar", "dpkg", "rpm2cpio"]
+        vec!["bsdtar", "ar", "dpkg"]
     }
 
     fn build_command(
         &self,
         binary: &Path,
         file: &Path,
+        _format: &FileFormat,
+        output_dir: &Path,
+        _verbose: bool,
+    ) -> Result<Command> {
+        let mut cmd = Command::new(binary);
+        let bin_name = binary.file_name().unwrap().to_str().unwrap();
+
+        // bsdtar and ar only see the debian-binary, control.tar and data.tar
+        // members of the package, the payload is streamed from data.tar into
+        // a second tar by build_pipeline
+        match bin_name {
+            "bsdtar" => {
+                cmd.arg("-x").arg("-O").arg("-f").arg(file);
+            }
+            "ar" => {
+                cmd.arg("p").arg(file);
+            }
+            "dpkg" => {
+                cmd.arg("-x").arg(file).arg(output_dir);
+            }
+            _ => return Err(self.unsupported_backend(bin_name)),
+        }
+        Ok(cmd)
+    }
+
+    fn build_pipeline(
+        &self,
+        binary: &Path,
+        file: &Path,
+        format: &FileFormat,
+        output_dir: &Path,
+        verbose: bool,
+    ) -> Result<Pipeline> {
+        let mut cmd = self.build_command(binary, file, format, output_dir, verbose)?;
+        if binary.ends_with("dpkg") {
+            return Ok(Pipeline::new(cmd));
+        }
+
+        let member = find_member(file, "data.tar")?;
+        cmd.arg(&member);
+
+        Ok(Pipeline::new(cmd).pipe(tar_from_stdin(&member, output_dir, verbose)?))
+    }
+
+    fn after_extract(&self, file: &Path, output_dir: &Path, verbose: bool) -> Result<()> {
+        package::write_control_meta(file, output_dir, verbose)
+    }
+}
+
+pub struct RpmExtractor;
+
+impl Extractor for RpmExtractor {
+    fn file_extensions(&self) -> Vec<&'static str> {
+        vec!["rpm"]
+    }
+
+    fn binary_names(&self) -> Vec<&'static str> {
+        vec!["bsdtar", "rpm2cpio"]
+    }
+
+    fn build_command(
+        &self,
+        binary: &Path,
+        file: &Path,
+        _format: &FileFormat,
         output_dir: &Path,
         verbose: bool,
     ) -> Result<Command> {
@@ -31,22 +101,187 @@ impl Extractor for DebRpmExtractor {
                     cmd.arg("-v");
                 }
             }
-            "dpkg" => {
-                cmd.arg("-x").arg(file).arg(output_dir);
-            }
             "rpm2cpio" => {
-                cmd.arg(file)
-                    .arg("|")
-                    .arg("cpio")
-                    .arg("-idm")
-                    .arg("-D")
-                    .arg(output_dir);
+                cmd.arg(file);
             }
-            "ar" => {
-                cmd.arg("x").arg(file).arg("--output").arg(output_dir);
-            }
-            _ => anyhow::bail!("Unsupported package tool: {}", bin_name),
+            _ => return Err(self.unsupported_backend(bin_name)),
         }
         Ok(cmd)
     }
+
+    fn build_pipeline(
+        &self,
+        binary: &Path,
+        file: &Path,
+        format: &FileFormat,
+        output_dir: &Path,
+        verbose: bool,
+    ) -> Result<Pipeline> {
+        let cmd = self.build_command(binary, file, format, output_dir, verbose)?;
+        if !binary.ends_with("rpm2cpio") {
+            return Ok(Pipeline::new(cmd));
+        }
+
+        Ok(Pipeline::new(cmd).pipe(cpio_from_stdin(output_dir, verbose)?))
+    }
+
+    fn after_extract(&self, file: &Path, output_dir: &Path, _verbose: bool) -> Result<()> {
+        package::write_rpm_meta(file, output_dir)
+    }
+}
+
+/// The first installed tool in `names`, or an error naming all of them.
+pub(crate) fn require_binary(format: &str, names: &[&str]) -> Result<PathBuf> {
+    names
+        .iter()
+        .find_map(|name| find_trusted_binary(name))
+        .ok_or_else(|| Error::NoToolInstalled {
+            format: format.to_string(),
+            tools: names.iter().map(|name| name.to_string()).collect(),
+        })
+}
+
+/// A tar command reading the tarball `member` from stdin, decompressing it
+/// as its extension calls for.
+pub(crate) fn tar_stdin(member: &str) -> Result<Command> {
+    let mut tar = Command::new(require_binary("tar", &["tar", "gtar", "bsdtar"])?);
+    tar.arg("-f").arg("-");
+
+    match member.rsplit('.').next() {
+        Some("gz") => {
+            tar.arg("-z");
+        }
+        Some("bz2") => {
+            tar.arg("-j");
+        }
+        Some("xz") => {
+            tar.arg("-J");
+        }
+        Some("zst") => {
+            tar.arg("--zstd");
+        }
+        Some("lzma") => {
+            tar.arg("--lzma");
+        }
+        _ => {}
+    }
+
+    Ok(tar)
+}
+
+/// A tar command that unpacks the tarball `member` read from stdin.
+pub(crate) fn tar_from_stdin(member: &str, output_dir: &Path, verbose: bool) -> Result<Command> {
+    let mut tar = tar_stdin(member)?;
+    tar.arg("-x").arg("-C").arg(output_dir);
+
+    if verbose {
+        tar.arg("-v");
+    }
+
+    Ok(tar)
+}
+
+/// A cpio command that unpacks the archive read from stdin into
+/// `output_dir`.
+pub(crate) fn cpio_from_stdin(output_dir: &Path, verbose: bool) -> Result<Command> {
+    let binary = require_binary("cpio", &["cpio", "bsdtar"])?;
+    let mut cpio = Command::new(&binary);
+
+    if binary.ends_with("bsdtar") {
+        cpio.arg("-x").arg("-f").arg("-").arg("-C").arg(output_dir);
+    } else {
+        cpio.arg("-idm")
+            .args(confine_args(&binary))
+            .current_dir(output_dir);
+    }
+
+    if verbose {
+        cpio.arg("-v");
+    }
+
+    Ok(cpio)
+}
+
+/// Whether `file` is an `ar` archive, like `.deb` and older `.ipk` packages.
+pub(crate) fn is_ar(file: &Path) -> Result<bool> {
+    let mut magic = [0u8; 8];
+    let read = File::open(file)?.read(&mut magic)?;
+    Ok(read == magic.len() && &magic == b"!<arch>\n")
+}
+
+/// Name of the first member of a `.deb` or `.ipk` starting with `prefix`,
+/// such as `data.tar`. Packages that are tarballs name their members
+/// `./data.tar.gz`, which matches as well.
+pub(crate) fn find_member(file: &Path, prefix: &str) -> Result<String> {
+    let members = if is_ar(file)? {
+        ar_members(file)?
+    } else {
+        let mut tar = Command::new(require_binary("tar", &["tar", "gtar", "bsdtar"])?);
+        tar.arg("-t").arg("-f").arg(file);
+        String::from_utf8_lossy(&capture_pipeline(Pipeline::new(tar))?)
+            .lines()
+            .map(str::to_string)
+            .collect()
+    };
+
+    members
+        .into_iter()
+        .find(|member| member.trim_start_matches("./").starts_with(prefix))
+        .ok_or_else(|| Error::UnsupportedFormat {
+            file: file.to_path_buf(),
+        })
+}
+
+/// A command writing `member` of the package `file` to stdout with `ar`,
+/// `bsdtar` or `tar`.
+pub(crate) fn member_command(binary: &Path, file: &Path, member: &str) -> Command {
+    let mut cmd = Command::new(binary);
+    if binary.ends_with("ar") {
+        cmd.arg("p").arg(file).arg(member);
+    } else {
+        cmd.arg("-x").arg("-O").arg("-f").arg(file).arg(member);
+    }
+    cmd
+}
+
+/// Lists the member names of an `ar` archive such as a `.deb` package.
+pub(crate) fn ar_members(file: &Path) -> Result<Vec<String>> {
+    let mut reader = BufReader::new(File::open(file)?);
+
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != b"!<arch>\n" {
+        return Err(Error::UnsupportedFormat {
+            file: file.to_path_buf(),
+        });
+    }
+
+    let mut members = Vec::new();
+    let mut header = [0u8; 60];
+    loop {
+        match reader.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(error) if error.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(error) => return Err(error.into()),
+        }
+
+        let name = String::from_utf8_lossy(&header[..16])
+            .trim_end()
+            .trim_end_matches('/')
+            .to_string();
+        let size: u64 = String::from_utf8_lossy(&header[48..58])
+            .trim()
+            .parse()
+            .map_err(|_| Error::UnsupportedFormat {
+                file: file.to_path_buf(),
+            })?;
+
+        std::io::copy(
+            &mut reader.by_ref().take(size + size % 2),
+            &mut std::io::sink(),
+        )?;
+        members.push(name);
+    }
+
+    Ok(members)
 }
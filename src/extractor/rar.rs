@@ -1,5 +1,5 @@
-use super::Extractor;
-use anyhow::Result;
+use super::{Extractor, FileFormat};
+use crate::error::Result;
 use std::path::Path;
 use std::process::Command;
 
@@ -25,6 +25,7 @@ impl Extractor for RarExtractor {
         &self,
         binary: &Path,
         file: &Path,
+        _format: &FileFormat,
         output_dir: &Path,
         verbose: bool,
     ) -> Result<Command> {
@@ -53,7 +54,7 @@ impl Extractor for RarExtractor {
                     cmd.arg("-bso0").arg("-bd");
                 }
             }
-            _ => anyhow::bail!("Unsupported RAR tool: {}", bin_name),
+            _ => return Err(self.unsupported_backend(bin_name)),
         }
         Ok(cmd)
     }
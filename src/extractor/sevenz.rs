@@ -1,5 +1,5 @@
-use super::Extractor;
-use anyhow::Result;
+use super::{Extractor, FileFormat};
+use crate::error::Result;
 use std::path::Path;
 use std::process::Command;
 
@@ -25,13 +25,13 @@ impl Extractor for SevenzExtractor {
         &self,
         binary: &Path,
         file: &Path,
+        _format: &FileFormat,
         output_dir: &Path,
         verbose: bool,
     ) -> Result<Command> {
-        let binary_name = binary
-            .file_name()
-            .and_then(|n| n.to_str())
-            .ok_or_else(|| anyhow::anyhow!("Invalid binary path"))?;
+        let binary_name = binary.file_name().and_then(|n| n.to_str()).ok_or_else(|| {
+            self.unsupported_backend(&binary.file_name().unwrap_or_default().to_string_lossy())
+        })?;
 
         let mut cmd = Command::new(binary);
 
@@ -59,7 +59,7 @@ impl Extractor for SevenzExtractor {
                     cmd.arg("-q");
                 }
             }
-            _ => anyhow::bail!("Unsupported 7z extraction tool: {}", binary_name),
+            _ => return Err(self.unsupported_backend(binary_name)),
         }
 
         Ok(cmd)
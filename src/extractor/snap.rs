@@ -0,0 +1,29 @@
+use super::squashfs::SquashfsExtractor;
+use super::{Extractor, FileFormat};
+use crate::error::Result;
+use std::path::Path;
+use std::process::Command;
+
+/// Snap packages, which are plain squashfs images.
+pub struct SnapExtractor;
+
+impl Extractor for SnapExtractor {
+    fn file_extensions(&self) -> Vec<&'static str> {
+        vec!["snap"]
+    }
+
+    fn binary_names(&self) -> Vec<&'static str> {
+        SquashfsExtractor.binary_names()
+    }
+
+    fn build_command(
+        &self,
+        binary: &Path,
+        file: &Path,
+        format: &FileFormat,
+        output_dir: &Path,
+        verbose: bool,
+    ) -> Result<Command> {
+        SquashfsExtractor.build_command(binary, file, format, output_dir, verbose)
+    }
+}
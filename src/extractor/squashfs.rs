@@ -0,0 +1,49 @@
+use super::{Extractor, FileFormat};
+use crate::error::Result;
+use std::path::Path;
+use std::process::Command;
+
+pub struct SquashfsExtractor;
+
+impl Extractor for SquashfsExtractor {
+    fn file_extensions(&self) -> Vec<&'static str> {
+        vec!["squashfs", "sqfs", "sfs"]
+    }
+
+    fn binary_names(&self) -> Vec<&'static str> {
+        vec!["unsquashfs", "7z"]
+    }
+
+    fn build_command(
+        &self,
+        binary: &Path,
+        file: &Path,
+        _format: &FileFormat,
+        output_dir: &Path,
+        verbose: bool,
+    ) -> Result<Command> {
+        let mut cmd = Command::new(binary);
+        let bin_name = binary.file_name().unwrap().to_str().unwrap();
+
+        match bin_name {
+            "unsquashfs" => {
+                cmd.arg("-f").arg("-d").arg(output_dir);
+                if !verbose {
+                    cmd.arg("-q").arg("-no-progress");
+                }
+                cmd.arg(file);
+            }
+            "7z" => {
+                cmd.arg("x")
+                    .arg("-y")
+                    .arg(format!("-o{}", output_dir.display()))
+                    .arg(file);
+                if !verbose {
+                    cmd.arg("-bso0").arg("-bd");
+                }
+            }
+            _ => return Err(self.unsupported_backend(bin_name)),
+        }
+        Ok(cmd)
+    }
+}
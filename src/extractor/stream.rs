@@ -0,0 +1,117 @@
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use filetime::FileTime;
+
+use crate::error::{Error, Result};
+use crate::trust::{run_pipeline_to_file, Pipeline};
+
+/// Compound extensions that stand for a gzip compressed file of another
+/// type. Tarball shorthands such as `.tgz` are extracted as archives.
+const RENAMED_EXTENSIONS: &[(&str, &str)] = &[("svgz", "svg"), ("emz", "emf"), ("wmz", "wmf")];
+
+/// Where the decompressed stream of `file` is written: `.gz` and friends are
+/// dropped and `.svgz` becomes `.svg`. Files without a recognised extension
+/// get `.out` appended.
+pub fn output_path(file: &Path, output_dir: &Path, extensions: &[&str]) -> PathBuf {
+    let file_name = file.file_name().unwrap_or_default();
+    let stem = file.file_stem().unwrap_or_default();
+    let extension = file
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    let name = if let Some((_, renamed)) = RENAMED_EXTENSIONS
+        .iter()
+        .find(|(compound, _)| *compound == extension)
+    {
+        let mut name = OsString::from(stem);
+        name.push(".");
+        name.push(renamed);
+        name
+    } else if !stem.is_empty() && extensions.contains(&extension.as_str()) {
+        stem.to_os_string()
+    } else {
+        let mut name = file_name.to_os_string();
+        name.push(".out");
+        name
+    };
+
+    output_dir.join(name)
+}
+
+/// Refuses to replace an existing file with the decompressed stream.
+pub fn check_output(target: &Path) -> Result<()> {
+    if target.symlink_metadata().is_ok() {
+        return Err(Error::OutputExists {
+            path: target.to_path_buf(),
+        });
+    }
+    Ok(())
+}
+
+/// Runs `pipeline`, which writes the decompressed `file` to stdout, into a
+/// temporary file next to `target`. The result is only moved into place
+/// when the tool succeeds and gets the modification time of `file`.
+pub fn run_stream(pipeline: Pipeline, file: &Path, target: &Path, verbose: bool) -> Result<()> {
+    let mut partial_name = OsString::from(".");
+    partial_name.push(target.file_name().unwrap_or_default());
+    partial_name.push(".extr-partial");
+    let partial = target.with_file_name(partial_name);
+
+    if let Err(error) = run_pipeline_to_file(pipeline, &partial, verbose) {
+        let _ = fs::remove_file(&partial);
+        return Err(error);
+    }
+
+    let finish = || -> Result<()> {
+        let mtime = FileTime::from_last_modification_time(&fs::metadata(file)?);
+        filetime::set_file_mtime(&partial, mtime)?;
+        check_output(target)?;
+        fs::rename(&partial, target)?;
+        Ok(())
+    };
+
+    let result = finish();
+    if result.is_err() {
+        let _ = fs::remove_file(&partial);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output_name(file: &str, extensions: &[&str]) -> PathBuf {
+        output_path(Path::new(file), Path::new("out"), extensions)
+    }
+
+    #[test]
+    fn renames_compound_extensions() {
+        let gzip = ["gz", "svgz", "emz", "wmz"];
+        assert_eq!(output_name("logo.svgz", &gzip), Path::new("out/logo.svg"));
+        assert_eq!(output_name("LOGO.SVGZ", &gzip), Path::new("out/LOGO.svg"));
+        assert_eq!(output_name("chart.emz", &gzip), Path::new("out/chart.emf"));
+        assert_eq!(output_name("clip.wmz", &gzip), Path::new("out/clip.wmf"));
+    }
+
+    #[test]
+    fn drops_the_compression_extension() {
+        assert_eq!(
+            output_name("notes.txt.gz", &["gz"]),
+            Path::new("out/notes.txt")
+        );
+        assert_eq!(output_name("dir/data.XZ", &["xz"]), Path::new("out/data"));
+    }
+
+    #[test]
+    fn appends_out_to_unknown_extensions() {
+        assert_eq!(
+            output_name("data.bin", &["gz"]),
+            Path::new("out/data.bin.out")
+        );
+        assert_eq!(output_name(".gz", &["gz"]), Path::new("out/.gz.out"));
+    }
+}
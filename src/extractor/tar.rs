@@ -1,15 +1,19 @@
 use std::{path::Path, process::Command};
 
-use anyhow::Result;
+use crate::error::{Error, Result};
+use crate::trust::Pipeline;
 
-use super::Extractor;
+use super::compression::{decoder_pipeline, Compression};
+use super::{Extractor, FileFormat};
 
 pub struct TarExtractor;
 
 impl Extractor for TarExtractor {
     fn file_extensions(&self) -> Vec<&'static str> {
         vec![
-            "tar", "tar.gz", "tgz", "tar.bz2", "tbz2", "tar.xz", "txz", "tar.zst",
+            "tar", "tar.gz", "tgz", "tar.bz2", "tar.bz", "tbz", "tbz2", "tb2", "tar.xz", "txz",
+            "tar.zst", "tzst", "tar.lzma", "tar.lz", "tlz", "tar.lzo", "tar.lz4", "tar.z", "taz",
+            "tar.br", "tar.sz",
         ]
     }
 
@@ -21,6 +25,7 @@ impl Extractor for TarExtractor {
         &self,
         binary: &Path,
         file: &Path,
+        format: &FileFormat,
         output_dir: &Path,
         verbose: bool,
     ) -> Result<Command> {
@@ -40,33 +45,78 @@ impl Extractor for TarExtractor {
                     cmd.arg("-v");
                 }
 
-                // Add compression flag based on file extension
-                if let Some(ext) = file.extension() {
-                    match ext.to_str().unwrap_or("") {
-                        "gz" | "tgz" => {
-                            cmd.arg("-z");
+                // Add compression flag based on the identified format
+                if let Some(compression) = format.compression.first() {
+                    match tar_flag(*compression) {
+                        Some(flag) => {
+                            cmd.arg(flag);
                         }
-                        "bz2" | "tbz2" => {
-                            cmd.arg("-j");
+                        None => {
+                            return Err(Error::UnsupportedFormat {
+                                file: file.to_path_buf(),
+                            });
                         }
-                        "xz" | "txz" => {
-                            cmd.arg("-J");
-                        }
-                        "zst" => {
-                            cmd.arg("--zstd");
-                        }
-                        "" => {}
-                        _ => {
-                            anyhow::bail!(
-                                "Unsupported tar extension tar.{}",
-                                ext.to_string_lossy()
-                            );
-                        }
-                    };
+                    }
                 }
             }
         }
 
         Ok(cmd)
     }
+
+    /// Compressors tar has no flag for are run as a separate decompressor
+    /// feeding the tarball to tar on stdin.
+    fn build_pipeline(
+        &self,
+        binary: &Path,
+        file: &Path,
+        format: &FileFormat,
+        output_dir: &Path,
+        verbose: bool,
+    ) -> Result<Pipeline> {
+        let compression = match format.compression.first() {
+            Some(compression) if tar_flag(*compression).is_none() && !binary.ends_with("unar") => {
+                *compression
+            }
+            _ => {
+                return self
+                    .build_command(binary, file, format, output_dir, verbose)
+                    .map(Pipeline::from)
+            }
+        };
+
+        Ok(decoder_pipeline(&[compression], file)?
+            .pipe(self.build_stdin_command(binary, format, output_dir, verbose)?))
+    }
+
+    fn stdin_binary_names(&self) -> Vec<&'static str> {
+        vec!["tar", "gtar", "bsdtar"]
+    }
+
+    fn build_stdin_command(
+        &self,
+        binary: &Path,
+        _format: &FileFormat,
+        output_dir: &Path,
+        verbose: bool,
+    ) -> Result<Command> {
+        let mut tar = Command::new(binary);
+        tar.arg("-xf").arg("-").arg("-C").arg(output_dir);
+        if verbose {
+            tar.arg("-v");
+        }
+        Ok(tar)
+    }
+}
+
+/// The flag both GNU tar and bsdtar understand for `compression`, if any.
+fn tar_flag(compression: Compression) -> Option<&'static str> {
+    match compression {
+        Compression::Gzip => Some("-z"),
+        Compression::Bzip2 => Some("-j"),
+        Compression::Xz => Some("-J"),
+        Compression::Zstd => Some("--zstd"),
+        Compression::Compress => Some("-Z"),
+        _ => None,
+    }
 }
@@ -0,0 +1,89 @@
+use super::{Extractor, FileFormat};
+use crate::error::Result;
+use crate::trust::{capture_pipeline, Pipeline};
+use std::path::Path;
+use std::process::Command;
+
+/// Windows imaging files, including the compressed `.esd` variant and
+/// `.swm` split images.
+pub struct WimExtractor;
+
+impl Extractor for WimExtractor {
+    fn file_extensions(&self) -> Vec<&'static str> {
+        vec!["wim", "esd", "swm"]
+    }
+
+    fn binary_names(&self) -> Vec<&'static str> {
+        vec!["wimlib-imagex", "7z"]
+    }
+
+    fn build_command(
+        &self,
+        binary: &Path,
+        file: &Path,
+        _format: &FileFormat,
+        output_dir: &Path,
+        verbose: bool,
+    ) -> Result<Command> {
+        let mut cmd = Command::new(binary);
+        let bin_name = binary.file_name().unwrap().to_str().unwrap();
+
+        match bin_name {
+            "wimlib-imagex" => {
+                cmd.arg("apply").arg(file);
+                // Images other than the only one go into subdirectories
+                // named after them
+                if image_count(binary, file) != Some(1) {
+                    cmd.arg("all");
+                }
+                cmd.arg(output_dir);
+                if let Some(parts) = split_parts(file) {
+                    cmd.arg(format!("--ref={parts}"));
+                }
+            }
+            "7z" => {
+                cmd.arg("x")
+                    .arg("-y")
+                    .arg(format!("-o{}", output_dir.display()))
+                    .arg(file);
+                if !verbose {
+                    cmd.arg("-bso0").arg("-bd");
+                }
+            }
+            _ => return Err(self.unsupported_backend(bin_name)),
+        }
+        Ok(cmd)
+    }
+}
+
+/// Number of images in the WIM, as reported by `wimlib-imagex info`.
+fn image_count(binary: &Path, file: &Path) -> Option<u32> {
+    let mut info = Command::new(binary);
+    info.arg("info").arg(file).arg("--header");
+    let output = capture_pipeline(Pipeline::new(info)).ok()?;
+
+    String::from_utf8_lossy(&output).lines().find_map(|line| {
+        let (key, value) = line.split_once('=').or_else(|| line.split_once(':'))?;
+        if key.trim().eq_ignore_ascii_case("image count") {
+            value.trim().parse().ok()
+        } else {
+            None
+        }
+    })
+}
+
+/// A glob matching every part of a split image such as `install.swm`,
+/// `install2.swm` and so on.
+fn split_parts(file: &Path) -> Option<String> {
+    let extension = file.extension()?.to_str()?;
+    if !extension.eq_ignore_ascii_case("swm") {
+        return None;
+    }
+    let stem = file.file_stem()?.to_str()?;
+    let base = stem.trim_end_matches(|c: char| c.is_ascii_digit());
+    Some(
+        file.with_file_name(format!("{base}*.{extension}"))
+            .display()
+            .to_string(),
+    )
+}
@@ -1,5 +1,5 @@
-use super::Extractor;
-use anyhow::Result;
+use super::{Extractor, FileFormat};
+use crate::error::Result;
 use std::path::Path;
 use std::process::Command;
 
@@ -25,6 +25,7 @@ impl Extractor for ZipExtractor {
         &self,
         binary: &Path,
         file: &Path,
+        _format: &FileFormat,
         output_dir: &Path,
         verbose: bool,
     ) -> Result<Command> {
@@ -62,7 +63,7 @@ impl Extractor for ZipExtractor {
                     cmd.arg("-v");
                 }
             }
-            _ => anyhow::bail!("Unsupported zip extraction tool: {}", binary_name),
+            _ => return Err(self.unsupported_backend(binary_name)),
         }
 
         Ok(cmd)
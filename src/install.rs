@@ -0,0 +1,199 @@
+//! Telling the user how to install missing tools with the package manager of
+//! their system.
+
+use std::fmt;
+use std::fs;
+
+/// Package managers extr knows the package names for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackageManager {
+    Apt,
+    Dnf,
+    Pacman,
+    Apk,
+    Zypper,
+    Brew,
+    Nix,
+}
+
+/// Packages providing each tool, in the order apt, dnf, pacman, apk, zypper,
+/// brew and nix. Empty where a tool is not packaged.
+#[rustfmt::skip]
+const PACKAGES: &[(&str, [&str; 7])] = &[
+    ("7z", ["p7zip-full", "p7zip-plugins", "7zip", "7zip", "7zip", "p7zip", "p7zip"]),
+    ("7za", ["p7zip-full", "p7zip", "7zip", "7zip", "7zip", "p7zip", "p7zip"]),
+    ("7zr", ["p7zip", "p7zip", "7zip", "7zip", "7zip", "p7zip", "p7zip"]),
+    ("unzip", ["unzip", "unzip", "unzip", "unzip", "unzip", "unzip", "unzip"]),
+    ("unrar", ["unrar", "unrar", "unrar", "unrar", "unrar", "", "unrar"]),
+    ("unar", ["unar", "unar", "unarchiver", "", "unar", "unar", "unar"]),
+    ("bsdtar", ["libarchive-tools", "bsdtar", "libarchive", "libarchive-tools", "bsdtar", "libarchive", "libarchive"]),
+    ("tar", ["tar", "tar", "tar", "tar", "tar", "gnu-tar", "gnutar"]),
+    ("gtar", ["", "", "", "", "", "gnu-tar", ""]),
+    ("gzip", ["gzip", "gzip", "gzip", "gzip", "gzip", "gzip", "gzip"]),
+    ("gunzip", ["gzip", "gzip", "gzip", "gzip", "gzip", "gzip", "gzip"]),
+    ("bzip2", ["bzip2", "bzip2", "bzip2", "bzip2", "bzip2", "bzip2", "bzip2"]),
+    ("bunzip2", ["bzip2", "bzip2", "bzip2", "bzip2", "bzip2", "bzip2", "bzip2"]),
+    ("xz", ["xz-utils", "xz", "xz", "xz", "xz", "xz", "xz"]),
+    ("lzma", ["xz-utils", "xz-lzma-compat", "xz", "xz", "xz", "xz", "xz"]),
+    ("unlzma", ["xz-utils", "xz-lzma-compat", "xz", "xz", "xz", "xz", "xz"]),
+    ("zstd", ["zstd", "zstd", "zstd", "zstd", "zstd", "zstd", "zstd"]),
+    ("lzip", ["lzip", "lzip", "lzip", "lzip", "lzip", "lzip", "lzip"]),
+    ("lunzip", ["lunzip", "", "", "", "", "", "lunzip"]),
+    ("lzop", ["lzop", "lzop", "lzop", "lzop", "lzop", "lzop", "lzop"]),
+    ("lz4", ["lz4", "lz4", "lz4", "lz4", "lz4", "lz4", "lz4"]),
+    ("brotli", ["brotli", "brotli", "brotli", "brotli", "brotli", "brotli", "brotli"]),
+    ("snzip", ["snzip", "", "", "", "", "", ""]),
+    ("uncompress", ["ncompress", "ncompress", "ncompress", "", "ncompress", "", "ncompress"]),
+    ("cabextract", ["cabextract", "cabextract", "cabextract", "cabextract", "cabextract", "cabextract", "cabextract"]),
+    ("lha", ["lhasa", "lhasa", "lhasa", "", "lhasa", "lha", "lhasa"]),
+    ("lhasa", ["lhasa", "lhasa", "lhasa", "", "lhasa", "", "lhasa"]),
+    ("arj", ["arj", "arj", "arj", "", "arj", "arj", "arj"]),
+    ("unsquashfs", ["squashfs-tools", "squashfs-tools", "squashfs-tools", "squashfs-tools", "squashfs", "squashfs", "squashfsTools"]),
+    ("cpio", ["cpio", "cpio", "cpio", "cpio", "cpio", "cpio", "cpio"]),
+    ("wimlib-imagex", ["wimtools", "wimlib-utils", "wimlib", "wimlib", "wimtools", "wimlib", "wimlib"]),
+    ("rpm2cpio", ["rpm2cpio", "rpm", "", "", "rpm", "rpm2cpio", "rpm"]),
+    ("dpkg", ["dpkg", "dpkg", "dpkg", "dpkg", "dpkg", "dpkg", "dpkg"]),
+    ("ar", ["binutils", "binutils", "binutils", "binutils", "binutils", "binutils", "binutils"]),
+    ("flatpak", ["flatpak", "flatpak", "flatpak", "flatpak", "flatpak", "", "flatpak"]),
+    ("ostree", ["ostree", "ostree", "ostree", "ostree", "ostree", "", "ostree"]),
+    ("jar", ["default-jdk-headless", "", "", "", "", "", ""]),
+];
+
+impl PackageManager {
+    /// Detects the package manager from `/etc/os-release`, then from the
+    /// package manager binaries on `PATH`, which is all there is on macOS.
+    pub fn detect() -> Option<PackageManager> {
+        let from_os_release = fs::read_to_string("/etc/os-release")
+            .ok()
+            .and_then(|os_release| PackageManager::from_os_release(&os_release));
+        if from_os_release.is_some() {
+            return from_os_release;
+        }
+
+        [
+            ("apt-get", PackageManager::Apt),
+            ("dnf", PackageManager::Dnf),
+            ("pacman", PackageManager::Pacman),
+            ("apk", PackageManager::Apk),
+            ("zypper", PackageManager::Zypper),
+            ("brew", PackageManager::Brew),
+            ("nix-env", PackageManager::Nix),
+        ]
+        .into_iter()
+        .find(|(binary, _)| which::which(binary).is_ok())
+        .map(|(_, manager)| manager)
+    }
+
+    /// Picks the package manager from the `ID` and `ID_LIKE` fields of an
+    /// os-release file.
+    pub fn from_os_release(os_release: &str) -> Option<PackageManager> {
+        let ids = os_release
+            .lines()
+            .filter_map(|line| {
+                line.strip_prefix("ID=")
+                    .or_else(|| line.strip_prefix("ID_LIKE="))
+            })
+            .flat_map(|value| value.trim_matches('"').split_whitespace())
+            .collect::<Vec<_>>();
+
+        ids.iter().find_map(|id| match *id {
+            "debian" | "ubuntu" => Some(PackageManager::Apt),
+            "fedora" | "rhel" | "centos" => Some(PackageManager::Dnf),
+            "arch" => Some(PackageManager::Pacman),
+            "alpine" => Some(PackageManager::Apk),
+            "suse" | "opensuse" => Some(PackageManager::Zypper),
+            "nixos" => Some(PackageManager::Nix),
+            id if id.starts_with("opensuse") => Some(PackageManager::Zypper),
+            _ => None,
+        })
+    }
+
+    /// The package providing `tool`, if it is packaged for this manager.
+    pub fn package(&self, tool: &str) -> Option<&'static str> {
+        PACKAGES
+            .iter()
+            .find(|(name, _)| *name == tool)
+            .map(|(_, packages)| packages[self.column()])
+            .filter(|package| !package.is_empty())
+    }
+
+    /// Where the packages of this manager are in [`PACKAGES`].
+    fn column(&self) -> usize {
+        match self {
+            PackageManager::Apt => 0,
+            PackageManager::Dnf => 1,
+            PackageManager::Pacman => 2,
+            PackageManager::Apk => 3,
+            PackageManager::Zypper => 4,
+            PackageManager::Brew => 5,
+            PackageManager::Nix => 6,
+        }
+    }
+
+    /// The shell command installing `packages`. Scripts need the
+    /// `non_interactive` variant, which does not ask for confirmation.
+    pub fn install_command(&self, packages: &[&str], non_interactive: bool) -> String {
+        let packages = match self {
+            PackageManager::Nix => packages
+                .iter()
+                .map(|package| format!("nixpkgs.{package}"))
+                .collect::<Vec<_>>()
+                .join(" "),
+            _ => packages.join(" "),
+        };
+
+        let command = match (self, non_interactive) {
+            (PackageManager::Apt, false) => "sudo apt install",
+            (PackageManager::Apt, true) => "sudo apt-get install -y",
+            (PackageManager::Dnf, false) => "sudo dnf install",
+            (PackageManager::Dnf, true) => "sudo dnf install -y",
+            (PackageManager::Pacman, false) => "sudo pacman -S",
+            (PackageManager::Pacman, true) => "sudo pacman -S --needed --noconfirm",
+            (PackageManager::Apk, _) => "sudo apk add",
+            (PackageManager::Zypper, false) => "sudo zypper install",
+            (PackageManager::Zypper, true) => "sudo zypper --non-interactive install",
+            (PackageManager::Brew, _) => "brew install",
+            (PackageManager::Nix, _) => "nix-env -iA",
+        };
+
+        format!("{command} {packages}")
+    }
+}
+
+impl fmt::Display for PackageManager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PackageManager::Apt => "apt",
+            PackageManager::Dnf => "dnf",
+            PackageManager::Pacman => "pacman",
+            PackageManager::Apk => "apk",
+            PackageManager::Zypper => "zypper",
+            PackageManager::Brew => "brew",
+            PackageManager::Nix => "nix",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn looks_up_packages_per_manager() {
+        assert_eq!(
+            PackageManager::Apt.package("bsdtar"),
+            Some("libarchive-tools")
+        );
+        assert_eq!(
+            PackageManager::Zypper.package("unsquashfs"),
+            Some("squashfs")
+        );
+        assert_eq!(PackageManager::Brew.package("tar"), Some("gnu-tar"));
+        assert_eq!(
+            PackageManager::Nix.package("unsquashfs"),
+            Some("squashfsTools")
+        );
+        assert_eq!(PackageManager::Brew.package("ostree"), None);
+        assert_eq!(PackageManager::Apt.package("not-a-tool"), None);
+    }
+}
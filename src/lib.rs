@@ -0,0 +1,561 @@
+//! The archive extractor that makes unpacking files as easy as typing their
+//! name.
+//!
+//! extr detects the archive format from the file name and hands the work to
+//! a trusted tool already installed on the system, such as `tar`, `unzip` or
+//! `7z`. The same machinery that powers the `extr` command line tool is
+//! available here through [`ExtractOptions`].
+//!
+//! ```
+//! use extr::ExtractOptions;
+//! # let dir = std::env::temp_dir().join(format!("extr-doc-extract-{}", std::process::id()));
+//! # std::fs::create_dir_all(&dir)?;
+//! # std::env::set_current_dir(&dir)?;
+//! # std::fs::write("notes.txt", "hello")?;
+//! # std::process::Command::new("tar").args(["-czf", "release.tar.gz", "notes.txt"]).status()?;
+//!
+//! let extraction = ExtractOptions::new()
+//!     .output_dir("unpacked")
+//!     .fallback(true)
+//!     .extract("release.tar.gz")?;
+//!
+//! println!("Extracted {} using {}", extraction.format, extraction.backend.display());
+//! # assert_eq!(std::fs::read_to_string("unpacked/notes.txt")?, "hello");
+//! # std::fs::remove_dir_all(&dir)?;
+//! # Ok::<(), extr::Error>(())
+//! ```
+//!
+//! Resolving the format and tool can be done up front, which makes it
+//! possible to validate a batch of files before extracting any of them:
+//!
+//! ```
+//! use extr::ExtractOptions;
+//! # let dir = std::env::temp_dir().join(format!("extr-doc-plan-{}", std::process::id()));
+//! # std::fs::create_dir_all(&dir)?;
+//! # std::env::set_current_dir(&dir)?;
+//! # std::fs::write("notes.txt", "hello")?;
+//! # for archive in ["a.tar.gz", "b.tar"] {
+//! #     std::process::Command::new("tar").args(["-caf", archive, "notes.txt"]).status()?;
+//! # }
+//!
+//! let options = ExtractOptions::new().backend("tar").output_dir("unpacked");
+//! let plans = ["a.tar.gz", "b.tar"]
+//!     .iter()
+//!     .map(|file| options.plan(file))
+//!     .collect::<extr::Result<Vec<_>>>()?;
+//!
+//! for plan in &plans {
+//!     options.run(plan)?;
+//! }
+//! # std::fs::remove_dir_all(&dir)?;
+//! # Ok::<(), extr::Error>(())
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+
+pub mod config;
+pub mod dispose;
+pub mod error;
+pub mod extractor;
+pub mod install;
+pub mod manifest;
+mod output;
+pub mod package;
+pub mod preserve;
+pub mod probe;
+pub mod report;
+pub mod trust;
+pub mod verify;
+pub mod watch;
+
+pub use config::Config;
+pub use dispose::Disposal;
+pub use error::{Error, FailureReason, Result};
+pub use extractor::{
+    get_extension, get_extractor, get_format_extractor, get_health_info, identify,
+    identify_contents, register_extractor, BackendSelection, Extractor, FileFormat, FormatHealth,
+};
+pub use output::Overwrite;
+pub use preserve::{Attribute, Preservation};
+pub use probe::Feature;
+pub use report::Report;
+pub use verify::{Checksum, Verification};
+
+/// Settings for extracting one or more archives.
+#[derive(Debug, Clone)]
+pub struct ExtractOptions {
+    output_dir: PathBuf,
+    backend: Option<String>,
+    preferred_backends: BTreeMap<String, Vec<String>>,
+    fallback: bool,
+    verbose: bool,
+    overwrite: Overwrite,
+    wrap: bool,
+    post_extract: Vec<String>,
+    preservation: Preservation,
+    verification: Verification,
+    disposal: Disposal,
+    on_retry: Option<RetryHook>,
+}
+
+type RetryFn = dyn Fn(&Path, &Error, &Path) + Send + Sync;
+
+/// Told about each failed tool that another one takes over from.
+#[derive(Clone)]
+struct RetryHook(Arc<RetryFn>);
+
+impl fmt::Debug for RetryHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("RetryHook")
+    }
+}
+
+impl Default for ExtractOptions {
+    fn default() -> Self {
+        Self {
+            output_dir: PathBuf::from("."),
+            backend: None,
+            preferred_backends: BTreeMap::new(),
+            fallback: false,
+            verbose: false,
+            overwrite: Overwrite::default(),
+            wrap: false,
+            post_extract: Vec::new(),
+            preservation: Preservation::default(),
+            verification: Verification::default(),
+            disposal: Disposal::default(),
+            on_retry: None,
+        }
+    }
+}
+
+impl ExtractOptions {
+    /// Extracts into the current directory with the default tool order.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Applies the settings from a loaded configuration. Trusted paths are
+    /// not part of the options, pass them to [`trust::add_trusted_dirs`].
+    pub fn with_config(mut self, config: &Config) -> Self {
+        self.preferred_backends.extend(config.backends.clone());
+        if let Some(output_dir) = &config.output_dir {
+            self.output_dir = output_dir.clone();
+        }
+        if let Some(overwrite) = config.overwrite {
+            self.overwrite = overwrite;
+        }
+        if let Some(wrap) = config.wrap {
+            self.wrap = wrap;
+        }
+        if let Some(verbose) = config.verbose {
+            self.verbose = verbose;
+        }
+        if let Some(fallback) = config.fallback {
+            self.fallback = fallback;
+        }
+        if let Some(post_extract) = &config.post_extract {
+            self.post_extract = post_extract.clone();
+        }
+        self
+    }
+
+    /// Directory to extract into, created if missing.
+    pub fn output_dir(mut self, output_dir: impl Into<PathBuf>) -> Self {
+        self.output_dir = output_dir.into();
+        self
+    }
+
+    /// Only use the tool `name`, e.g. `bsdtar`.
+    pub fn backend(mut self, name: impl Into<String>) -> Self {
+        self.backend = Some(name.into());
+        self
+    }
+
+    /// Try `tools` in order before the built-in order for `format`, e.g.
+    /// `zip` or `tar.gz`.
+    pub fn preferred_backends<I, S>(mut self, format: impl Into<String>, tools: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.preferred_backends
+            .insert(format.into(), tools.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Retry with the next installed tool when one fails.
+    pub fn fallback(mut self, fallback: bool) -> Self {
+        self.fallback = fallback;
+        self
+    }
+
+    /// Calls `hook` with the archive, the error and the next tool whenever
+    /// a failed tool is followed by the next one. Retries are silent
+    /// otherwise.
+    pub fn on_retry(mut self, hook: impl Fn(&Path, &Error, &Path) + Send + Sync + 'static) -> Self {
+        self.on_retry = Some(RetryHook(Arc::new(hook)));
+        self
+    }
+
+    /// Let the tool print its own progress.
+    pub fn verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    /// What to do when the output already exists.
+    pub fn overwrite(mut self, overwrite: Overwrite) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    /// Extract into a staging directory first, then move a single top level
+    /// entry in place as it is or wrap several entries in a directory named
+    /// after the archive.
+    pub fn wrap(mut self, wrap: bool) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    /// Commands to run after each extraction, `{file}` is replaced by the
+    /// archive and `{out}` by where its contents ended up.
+    pub fn post_extract<I, S>(mut self, commands: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.post_extract = commands.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Restore exactly `attributes` from the archives, resetting the others,
+    /// instead of leaving it to each tool.
+    pub fn preserve(mut self, attributes: impl IntoIterator<Item = Attribute>) -> Self {
+        self.preservation.preserve = Some(attributes.into_iter().collect());
+        self
+    }
+
+    /// Strip setuid and setgid bits and write permission for others from
+    /// everything extracted.
+    pub fn normalize(mut self, normalize: bool) -> Self {
+        self.preservation.normalize = normalize;
+        self
+    }
+
+    /// Refuse to extract archives that do not have `checksum`.
+    pub fn checksum(mut self, checksum: Checksum) -> Self {
+        self.verification.checksum = Some(checksum);
+        self
+    }
+
+    /// Check archives against a `FILE.sha256` or `SHA256SUMS` file next to
+    /// them when there is one, on by default.
+    pub fn checksum_files(mut self, checksum_files: bool) -> Self {
+        self.verification.checksum_files = checksum_files;
+        self
+    }
+
+    /// Require a valid detached `.minisig`, `.asc` or `.sig` signature of
+    /// each archive, or of the checksum file listing it.
+    pub fn signature(mut self, signature: bool) -> Self {
+        self.verification.signature = signature;
+        self
+    }
+
+    /// Public key to check signatures with: a minisign public key, an
+    /// OpenSSH allowed signers file or a binary gpg keyring.
+    pub fn signing_key(mut self, key: impl Into<PathBuf>) -> Self {
+        self.verification.signing_key = Some(key.into());
+        self
+    }
+
+    /// What to do with each archive, and the other volumes of its set,
+    /// once it has been extracted and the post-extract commands succeeded.
+    pub fn dispose(mut self, disposal: Disposal) -> Self {
+        self.disposal = disposal;
+        self
+    }
+
+    /// The backend selection these options describe.
+    pub fn backend_selection(&self) -> Result<BackendSelection> {
+        BackendSelection::new(self.backend.clone(), self.preferred_backends.clone())
+    }
+
+    /// Detects the format of `file`, checks that a trusted tool is
+    /// installed for it and that it matches its checksum and signature,
+    /// without extracting anything.
+    pub fn plan(&self, file: impl AsRef<Path>) -> Result<Plan> {
+        let file = file.as_ref();
+        let selection = self.backend_selection()?;
+
+        // Fall back to the contents for names that give nothing away
+        let (identified, extractor) = identify(file)
+            .and_then(|format| get_format_extractor(&format).map(|found| (format, found)))
+            .or_else(|| {
+                let format = identify_contents(file)?;
+                get_format_extractor(&format).map(|found| (format, found))
+            })
+            .ok_or_else(|| Error::UnsupportedFormat {
+                file: file.to_path_buf(),
+            })?;
+
+        let format = identified.extension.clone();
+        let backends = selection.candidates_for(&identified, extractor)?;
+        let binary = extractor.get_verified_binary(&backends)?;
+        self.verification.verify(file, self.verbose)?;
+
+        Ok(Plan {
+            file: file.to_path_buf(),
+            format,
+            binary,
+            identified,
+            backends,
+            extractor,
+        })
+    }
+
+    /// Extracts a previously planned file.
+    pub fn run(&self, plan: &Plan) -> Result<Extraction> {
+        let started = Instant::now();
+        let mut extraction = Extraction {
+            file: plan.file.clone(),
+            output_dir: self.output_dir.clone(),
+            format: plan.format.clone(),
+            backend: plan.binary.clone(),
+            target: self.output_dir.clone(),
+            skipped: false,
+            report: Report::default(),
+            disposed: Vec::new(),
+        };
+
+        let changed = if plan.extractor.is_single_stream() {
+            extraction.target = extractor::stream::output_path(
+                &plan.file,
+                &self.output_dir,
+                &plan.extractor.file_extensions(),
+            );
+            if !output::clear_target(&extraction.target, self.overwrite)? {
+                extraction.skipped = true;
+                return Ok(extraction);
+            }
+            extraction.backend = self.extract_into(plan, &self.output_dir)?;
+            vec![extraction.target.clone()]
+        } else if self.wrap {
+            std::fs::create_dir_all(&self.output_dir)?;
+            let staging = output::staging_dir(&plan.file, &self.output_dir);
+            let result = self.extract_into(plan, &staging).and_then(|backend| {
+                let target = output::unwrap_staging(
+                    &staging,
+                    &plan.file,
+                    &plan.format,
+                    &self.output_dir,
+                    self.overwrite,
+                )?;
+                Ok((backend, target))
+            });
+            if staging.exists() {
+                output::remove_path(&staging)?;
+            }
+
+            let (backend, (target, placed)) = result?;
+            extraction.backend = backend;
+            extraction.target = target;
+            if !placed {
+                extraction.skipped = true;
+                return Ok(extraction);
+            }
+            // Empty archives leave nothing to wrap
+            if extraction.target == self.output_dir {
+                Vec::new()
+            } else {
+                output::tree(&extraction.target)?
+            }
+        } else {
+            std::fs::create_dir_all(&self.output_dir)?;
+            // Whatever a failed or stopped tool wrote goes away with the
+            // staging directory, without touching anything else
+            let staging = output::staging_dir(&plan.file, &self.output_dir);
+            let result = self.extract_into(plan, &staging).and_then(|backend| {
+                let placed = output::merge_staging(&staging, &plan.file, &self.output_dir)?;
+                Ok((backend, placed))
+            });
+            if staging.exists() {
+                output::remove_path(&staging)?;
+            }
+
+            let (backend, placed) = result?;
+            extraction.backend = backend;
+            let mut changed = Vec::new();
+            for path in &placed {
+                changed.extend(output::tree(path)?);
+            }
+            changed.sort();
+            changed
+        };
+
+        // Children first, a directory may lose the permissions to reach them
+        for path in changed.iter().rev() {
+            self.preservation.apply(path)?;
+        }
+        extraction.report = Report::collect(&self.output_dir, changed, started.elapsed())?;
+
+        output::run_post_extract(&self.post_extract, &extraction.file, &extraction.target)?;
+        extraction.disposed = self.disposal.apply(&extraction.file)?;
+        Ok(extraction)
+    }
+
+    fn extract_into(&self, plan: &Plan, output_dir: &Path) -> Result<PathBuf> {
+        plan.extractor.extract(
+            &plan.file,
+            &plan.identified,
+            output_dir,
+            &plan.backends,
+            self.fallback,
+            self.verbose,
+            &self.preservation,
+            &|error, next| {
+                if let Some(RetryHook(hook)) = &self.on_retry {
+                    hook(&plan.file, error, next);
+                }
+            },
+        )
+    }
+
+    /// Works out what extracting a previously planned file would run and
+    /// where the contents would end up, without running or writing anything.
+    pub fn dry_run(&self, plan: &Plan) -> Result<DryRun> {
+        let (target, staging) = if plan.extractor.is_single_stream() {
+            let target = extractor::stream::output_path(
+                &plan.file,
+                &self.output_dir,
+                &plan.extractor.file_extensions(),
+            );
+            (target, None)
+        } else {
+            let staging = output::staging_dir(&plan.file, &self.output_dir);
+            (self.output_dir.clone(), Some(staging))
+        };
+
+        let pipelines = plan.extractor.build_extraction(
+            &plan.binary,
+            &plan.file,
+            &plan.identified,
+            staging.as_deref().unwrap_or(&self.output_dir),
+            self.verbose,
+            &self.preservation,
+        )?;
+
+        let fallbacks = if self.fallback {
+            plan.extractor
+                .get_verified_binaries(&plan.backends)
+                .into_iter()
+                .filter(|binary| *binary != plan.binary)
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        let post_extract = self
+            .post_extract
+            .iter()
+            .map(|command| {
+                output::post_extract_words(command, &plan.file, &target).map(shell_words::join)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let disposed = match self.disposal {
+            Disposal::Keep => Vec::new(),
+            _ => dispose::volumes(&plan.file)?,
+        };
+
+        Ok(DryRun {
+            pipelines,
+            fallbacks,
+            to_target: plan.extractor.is_single_stream(),
+            target,
+            staging,
+            wrap: self.wrap,
+            post_extract,
+            disposed,
+        })
+    }
+
+    /// Plans and extracts `file` in one go.
+    pub fn extract(&self, file: impl AsRef<Path>) -> Result<Extraction> {
+        let plan = self.plan(file)?;
+        self.run(&plan)
+    }
+}
+
+/// A file whose format and extraction tool have been resolved.
+pub struct Plan {
+    /// The archive to extract
+    pub file: PathBuf,
+    /// Format identifier, e.g. `zip` or `tar.gz`
+    pub format: String,
+    /// The tool that will be tried first
+    pub binary: PathBuf,
+    identified: FileFormat,
+    backends: Vec<&'static str>,
+    extractor: &'static dyn Extractor,
+}
+
+/// What extracting a file would do, see [`ExtractOptions::dry_run`].
+#[derive(Debug)]
+pub struct DryRun {
+    /// What the first tool runs, one pipeline after another
+    pub pipelines: Vec<trust::Pipeline>,
+    /// Tools tried in turn when the first one fails
+    pub fallbacks: Vec<PathBuf>,
+    /// The decompressed file, or the directory the contents end up in
+    pub target: PathBuf,
+    /// Whether the output of the pipelines is written to `target`, as it is
+    /// for single-stream formats such as `.gz`
+    pub to_target: bool,
+    /// Directory the contents are extracted into first, before they are
+    /// moved into `target`
+    pub staging: Option<PathBuf>,
+    /// Whether several top level entries are wrapped in a directory
+    pub wrap: bool,
+    /// Commands run afterwards, quoted for a shell
+    pub post_extract: Vec<String>,
+    /// The archive and its other volumes when they would be deleted or
+    /// moved away afterwards
+    pub disposed: Vec<PathBuf>,
+}
+
+/// The outcome of a successful extraction.
+#[derive(Debug, Clone)]
+pub struct Extraction {
+    /// The archive that was extracted
+    pub file: PathBuf,
+    /// Where the contents were written
+    pub output_dir: PathBuf,
+    /// Format identifier, e.g. `zip` or `tar.gz`
+    pub format: String,
+    /// The tool that did the work
+    pub backend: PathBuf,
+    /// The decompressed file, the wrapped contents or else `output_dir`
+    pub target: PathBuf,
+    /// The output already existed and the archive was left alone
+    pub skipped: bool,
+    /// What was written, empty when skipped
+    pub report: Report,
+    /// The archive and its other volumes when they were deleted or moved
+    /// away afterwards
+    pub disposed: Vec<PathBuf>,
+}
+
+/// An empty directory for a test to work in, unique to the test process.
+#[cfg(test)]
+pub(crate) fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("extr-test-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
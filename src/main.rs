@@ -1,12 +1,20 @@
-use anyhow::{anyhow, Result};
-use clap::{ArgAction, Parser};
-use extractor::{get_extractor, register_extractors};
-use std::path::PathBuf;
+use clap::{ArgAction, Parser, Subcommand, ValueEnum};
+use extr::dispose::volumes;
+use extr::extractor::custom::register_custom_extractors;
+use extr::install::PackageManager;
+use extr::manifest::{verify_manifest, Manifest};
+use extr::package::read_package_info;
+use extr::preserve::{parse_attributes, Attribute};
+use extr::trust::{add_trusted_dirs, cancel_on_signals, set_timeout};
+use extr::watch::Inbox;
+use extr::{
+    get_health_info, Checksum, Config, Disposal, DryRun, Error, ExtractOptions, Extraction,
+    FormatHealth, Overwrite, Plan, Result,
+};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
 use std::process::exit;
-use std::str::FromStr;
-
-mod extractor;
-mod trust;
+use std::time::Duration;
 
 /// Extract compressed archives automatically
 #[derive(Parser, Debug)]
@@ -27,15 +35,21 @@ Examples:
   extr file.zip                Extract to current directory
   extr *.tar.gz -o ~/unpacked  Extract multiple archives
   extr --health                Verify system compatibility
+  extr config show             Show the settings in effect and their origin
+  extr watch ~/inbox -o ~/out  Extract archives as they arrive
 
 📦 Supports 30+ archive formats | 🚀 No flags, no hazzle
     "#,
     about = "Forget the tar flags tango - just type the filename and extract!",
     version,
     author = "Fredrik Söderström <tirithen@gmail.com>",
-    long_about = "The archive extractor that makes unpacking files as easy as typing their name. No more memorizing complex flags or commands - just point extr at any archive and let the magic happen. Supporting 30+ formats, extr is the Swiss Army knife for all your file extraction needs."
+    long_about = "The archive extractor that makes unpacking files as easy as typing their name. No more memorizing complex flags or commands - just point extr at any archive and let the magic happen. Supporting 30+ formats, extr is the Swiss Army knife for all your file extraction needs.",
+    args_conflicts_with_subcommands = true
 )]
 struct Args {
+    #[clap(subcommand)]
+    command: Option<Commands>,
+
     /// Archive files to extract
     #[clap(name = "FILE")]
     files: Vec<PathBuf>,
@@ -44,94 +58,791 @@ struct Args {
     #[clap(long, action = ArgAction::SetTrue)]
     health: bool,
 
+    /// With --health, print a shell script installing the missing tools
+    /// with the system package manager
+    #[clap(long, action = ArgAction::SetTrue, requires = "health")]
+    fix_script: bool,
+
+    /// Show name, version, architecture, dependencies and files of .deb and
+    /// .rpm packages instead of extracting them
+    #[clap(long, action = ArgAction::SetTrue)]
+    info: bool,
+
+    /// Write the path, size, mode and SHA-256 of everything extracted, and
+    /// the hash of each archive, to FILE. Written as JSON for .json files
+    /// and in the sha256sum format otherwise
+    #[clap(long, value_name = "FILE")]
+    manifest: Option<PathBuf>,
+
+    /// Refuse to extract unless the archive has this checksum, e.g.
+    /// sha256:9f86d08...
+    #[clap(long, value_name = "ALGORITHM:HEX")]
+    checksum: Option<Checksum>,
+
+    /// Print the commands that would run and where the contents would end
+    /// up, without extracting or writing anything
+    #[clap(long, action = ArgAction::SetTrue)]
+    dry_run: bool,
+
+    #[clap(flatten)]
+    extract: ExtractArgs,
+}
+
+/// How archives are extracted, shared by extracting files given on the
+/// command line and watching a directory
+#[derive(clap::Args, Debug)]
+struct ExtractArgs {
+    /// Use this tool to extract, e.g. bsdtar, unzip or 7z
+    #[clap(short, long, value_name = "NAME")]
+    backend: Option<String>,
+
+    /// Retry with the next installed tool when one fails
+    #[clap(long, action = ArgAction::SetTrue)]
+    fallback: bool,
+
     /// Extract to a specific directory
     #[clap(short, long, value_name = "DIR")]
     output_dir: Option<PathBuf>,
 
+    /// What to do when the output already exists: refuse, replace or skip
+    #[clap(long, value_name = "POLICY")]
+    overwrite: Option<Overwrite>,
+
+    /// Wrap archives with several top level entries in a directory named
+    /// after the archive
+    #[clap(long, action = ArgAction::SetTrue, conflicts_with = "no_wrap")]
+    wrap: bool,
+
+    /// Extract archives as they are, even when wrapping is configured
+    #[clap(long, action = ArgAction::SetTrue)]
+    no_wrap: bool,
+
+    /// Restore exactly these attributes from the archive and reset the
+    /// others: a comma separated list of mode, owner, mtime, xattrs and acl,
+    /// or all or none
+    #[clap(long, value_name = "ATTRS", value_parser = parse_attributes)]
+    preserve: Option<BTreeSet<Attribute>>,
+
+    /// Strip setuid, setgid and world-writable permissions from extracted
+    /// files
+    #[clap(long, action = ArgAction::SetTrue)]
+    normalize: bool,
+
+    /// Summarize what each archive created, as text or with --report=json
+    /// as one JSON object per line
+    #[clap(
+        long,
+        value_name = "FORMAT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "human"
+    )]
+    report: Option<ReportFormat>,
+
+    /// Do not check archives against FILE.sha256 or SHA256SUMS files next
+    /// to them
+    #[clap(long, action = ArgAction::SetTrue)]
+    no_checksum_files: bool,
+
+    /// Require a valid detached .minisig, .asc or .sig signature of each
+    /// archive or of the checksum file listing it, checked with minisign,
+    /// ssh-keygen or gpg
+    #[clap(long, action = ArgAction::SetTrue)]
+    verify_signature: bool,
+
+    /// Key to check signatures with: a minisign public key, an OpenSSH
+    /// allowed signers file or a binary gpg keyring. gpg uses your own
+    /// keyring without one
+    #[clap(long, value_name = "FILE", requires = "verify_signature")]
+    signing_key: Option<PathBuf>,
+
+    /// Delete each archive, and all volumes of a multi-volume set, once it
+    /// was extracted successfully
+    #[clap(long, action = ArgAction::SetTrue, conflicts_with_all = ["trash", "move_to"])]
+    delete_after: bool,
+
+    /// Move each archive to the trash once it was extracted successfully
+    #[clap(long, action = ArgAction::SetTrue, conflicts_with = "move_to")]
+    trash: bool,
+
+    /// Move each archive into DIR once it was extracted successfully
+    #[clap(long, value_name = "DIR")]
+    move_to: Option<PathBuf>,
+
+    /// Stop tools that run longer than this, in seconds or with an s, m or
+    /// h suffix such as 10m
+    #[clap(long, value_name = "DURATION", value_parser = parse_duration)]
+    timeout: Option<Duration>,
+
     /// Verbose output
     #[clap(short, long, action = ArgAction::SetTrue)]
     verbose: bool,
 }
 
-fn main() -> Result<()> {
-    register_extractors();
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum ReportFormat {
+    Human,
+    Json,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Inspect the configuration
+    Config {
+        #[clap(subcommand)]
+        action: ConfigAction,
+    },
+    /// Extract archives as they land in a directory, once they stopped
+    /// growing
+    Watch {
+        /// Directory to watch
+        #[clap(name = "DIR")]
+        dir: PathBuf,
+
+        /// Extract the archives that are there now and exit, for cron
+        #[clap(long, action = ArgAction::SetTrue)]
+        once: bool,
+
+        #[clap(flatten)]
+        extract: ExtractArgs,
+    },
+    /// Check an extracted tree against a manifest written with --manifest
+    VerifyManifest {
+        /// The manifest, JSON or in the sha256sum format
+        #[clap(name = "FILE")]
+        manifest: PathBuf,
+
+        /// Directory the archives were extracted into
+        #[clap(short, long, value_name = "DIR", default_value = ".")]
+        dir: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigAction {
+    /// Print the settings in effect and where each one was set
+    Show,
+}
 
+fn main() {
     let args = Args::parse();
 
+    if args.files.is_empty() && !args.health && args.command.is_none() {
+        eprintln!("💥 Whoops! At least one FILE is required. Run $ extr --help for details.");
+        exit(2);
+    }
+
+    if args.checksum.is_some() && args.files.len() > 1 {
+        eprintln!("💥 Whoops! --checksum applies to a single FILE.");
+        exit(2);
+    }
+
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(error) => fail(error, args.extract_args().verbose),
+    };
+
+    let verbose = args.extract_args().verbose || config.verbose.unwrap_or(false);
+    if let Err(error) = run(args, config) {
+        fail(error, verbose);
+    }
+}
+
+impl Args {
+    /// The extraction flags, which follow `watch DIR` when watching.
+    fn extract_args(&self) -> &ExtractArgs {
+        match &self.command {
+            Some(Commands::Watch { extract, .. }) => extract,
+            _ => &self.extract,
+        }
+    }
+}
+
+fn fail(error: Error, verbose: bool) -> ! {
+    print_error(&error, verbose);
+    exit(error.exit_code());
+}
+
+fn print_error(error: &Error, verbose: bool) {
+    if let Error::BackendFailed { stderr, .. } = error {
+        if !verbose && !stderr.trim().is_empty() {
+            eprintln!("{}", stderr.trim_end());
+        }
+    }
+    eprintln!("{}", humanize_error(error));
+}
+
+fn run(args: Args, config: Config) -> Result<()> {
+    match &args.command {
+        Some(Commands::Config {
+            action: ConfigAction::Show,
+        }) => {
+            print_config(&config);
+            return Ok(());
+        }
+        Some(Commands::VerifyManifest { manifest, dir }) => {
+            let entries = verify_manifest(manifest, dir)?;
+            println!(
+                "✅ {} matches {}, {entries} entries checked",
+                dir.display(),
+                manifest.display()
+            );
+            return Ok(());
+        }
+        Some(Commands::Watch { .. }) | None => {}
+    }
+
+    add_trusted_dirs(config.trusted_paths.as_deref().unwrap_or_default());
+    cancel_on_signals();
+    register_custom_extractors(&config.extractors)?;
+
+    let extract = args.extract_args();
+    set_timeout(extract.timeout);
+    let verbose = extract.verbose || config.verbose.unwrap_or(false);
+    let fallback = extract.fallback || config.fallback.unwrap_or(false);
+
+    let mut options = ExtractOptions::new()
+        .with_config(&config)
+        .fallback(fallback)
+        .verbose(verbose)
+        .on_retry(move |file, error, next| {
+            print_error(error, verbose);
+            eprintln!("⚠️  Retrying {} with {}", file.display(), next.display());
+        });
+    if let Some(backend) = &extract.backend {
+        options = options.backend(backend);
+    }
+    if let Some(output_dir) = &extract.output_dir {
+        options = options.output_dir(output_dir);
+    }
+    if let Some(overwrite) = extract.overwrite {
+        options = options.overwrite(overwrite);
+    }
+    if extract.wrap || extract.no_wrap {
+        options = options.wrap(extract.wrap);
+    }
+    if let Some(preserve) = &extract.preserve {
+        options = options.preserve(preserve.iter().copied());
+    }
+    options = options.normalize(extract.normalize);
+    if let Some(checksum) = &args.checksum {
+        options = options.checksum(checksum.clone());
+    }
+    options = options
+        .checksum_files(!extract.no_checksum_files)
+        .signature(extract.verify_signature);
+    if let Some(key) = &extract.signing_key {
+        options = options.signing_key(key);
+    }
+    let disposal = if extract.delete_after {
+        Disposal::Delete
+    } else if extract.trash {
+        Disposal::Trash
+    } else if let Some(dir) = &extract.move_to {
+        Disposal::MoveTo(dir.clone())
+    } else {
+        Disposal::Keep
+    };
+    // Archives have to stay until the manifest recording them is written
+    if args.manifest.is_none() {
+        options = options.dispose(disposal.clone());
+    }
+
+    if let Some(Commands::Watch { dir, once, .. }) = &args.command {
+        return watch(dir, *once, &options, extract.report, verbose);
+    }
+
     if args.health {
-        return print_health_check();
+        let health_info = get_health_info(&options.backend_selection()?);
+        if args.fix_script {
+            print_fix_script(&health_info);
+            return Ok(());
+        }
+        return print_health_check(health_info);
     }
 
-    if args.files.is_empty() {
-        return Err(anyhow!(
-            "💥 Whoops! At least one FILE is required. Run $ extr --help for details."
-        ));
+    if args.info {
+        return print_package_info(&args.files);
     }
 
-    let extractors = args
+    let plans = args
         .files
         .iter()
-        .map(|file| match get_extractor(file) {
-            Some(extractor) => match extractor.get_verified_binary() {
-                Ok(_) => Ok((file, extractor)),
-                Err(error) => Err(error),
-            },
-            None => Err(anyhow!(
-                "💥 Whoops! Unable to find a supported extractor for {}, is the file an archive?",
-                file.to_string_lossy()
-            )),
-        })
+        .map(|file| options.plan(file))
         .collect::<Vec<_>>();
 
-    for result in &extractors {
-        if let Err(error) = result {
-            eprintln!("{error}");
-            exit(1);
+    let plans = plans.into_iter().collect::<Result<Vec<_>>>()?;
+
+    if args.dry_run {
+        for plan in &plans {
+            print_dry_run(plan, &options.dry_run(plan)?, extract);
+        }
+        return Ok(());
+    }
+
+    let Some(manifest_path) = &args.manifest else {
+        let mut disposed = BTreeSet::new();
+        for plan in plans {
+            // Later volumes of a set given on the command line went along
+            // with the first one
+            if disposed.contains(&plan.file) {
+                continue;
+            }
+            let extraction = options.run(&plan)?;
+            disposed.extend(extraction.disposed.iter().cloned());
+            print_extraction(&extraction, extract.report, verbose || fallback);
+        }
+        return Ok(());
+    };
+
+    let mut manifest = Manifest::default();
+    let mut extractions = Vec::new();
+    let mut volumes_done = BTreeSet::new();
+    for plan in plans {
+        if volumes_done.contains(&plan.file) {
+            continue;
+        }
+        let extraction = options.run(&plan)?;
+        if !extraction.skipped {
+            manifest.add(&extraction)?;
+            if disposal != Disposal::Keep {
+                volumes_done.extend(volumes(&extraction.file)?);
+            }
+        }
+        extractions.push(extraction);
+    }
+    manifest.write(manifest_path)?;
+
+    let mut disposed = BTreeSet::new();
+    for mut extraction in extractions {
+        if !extraction.skipped && !disposed.contains(&extraction.file) {
+            extraction.disposed = disposal.apply(&extraction.file)?;
+            disposed.extend(extraction.disposed.iter().cloned());
+        }
+        print_extraction(&extraction, extract.report, verbose || fallback);
+    }
+    Ok(())
+}
+
+/// Extracts archives as they land in `dir`. A failing archive is reported
+/// and the others are still extracted, with `once` the exit code is that of
+/// the first failure.
+fn watch(
+    dir: &Path,
+    once: bool,
+    options: &ExtractOptions,
+    report: Option<ReportFormat>,
+    verbose: bool,
+) -> Result<()> {
+    let mut inbox = Inbox::open(dir)?;
+    if !once {
+        println!("👀 Watching {} for archives", dir.display());
+    }
+
+    let mut first_failure = None;
+    let mut files = inbox.ready()?;
+    loop {
+        for file in files {
+            match options.extract(&file) {
+                Ok(extraction) => {
+                    inbox.skip(&extraction.report.entries);
+                    print_extraction(&extraction, report, true);
+                }
+                Err(error @ Error::Cancelled { .. }) => return Err(error),
+                Err(error) => {
+                    eprintln!("❌ Could not extract {}", file.display());
+                    print_error(&error, verbose);
+                    first_failure.get_or_insert(error.exit_code());
+                }
+            }
+        }
+        if once {
+            break;
         }
+        files = inbox.wait()?;
     }
 
-    let output_dir = args.output_dir.unwrap_or(PathBuf::from_str(".").unwrap());
-    for result in extractors {
-        let (file, extractor) = result?;
-        extractor.extract(file, &output_dir, args.verbose)?;
+    if let Some(code) = first_failure {
+        exit(code);
+    }
+    Ok(())
+}
+
+fn parse_duration(value: &str) -> std::result::Result<Duration, String> {
+    let (number, unit) = match value.trim().char_indices().last() {
+        Some((index, unit @ ('s' | 'm' | 'h'))) => (&value.trim()[..index], unit),
+        _ => (value.trim(), 's'),
+    };
+    let seconds = number
+        .parse::<f64>()
+        .ok()
+        .filter(|seconds| seconds.is_finite() && *seconds > 0.0)
+        .ok_or_else(|| format!("invalid duration {value}, expected e.g. 30, 90s, 10m or 1h"))?;
+    let factor = match unit {
+        'm' => 60.0,
+        'h' => 3600.0,
+        _ => 1.0,
+    };
+    Ok(Duration::from_secs_f64(seconds * factor))
+}
+
+fn print_dry_run(plan: &Plan, dry_run: &DryRun, extract: &ExtractArgs) {
+    use owo_colors::OwoColorize;
+
+    println!(
+        "🔍 {} ({}) with {}",
+        plan.file.display().bold(),
+        plan.format,
+        plan.binary.display()
+    );
+    for pipeline in &dry_run.pipelines {
+        if dry_run.to_target {
+            let target = dry_run.target.to_string_lossy();
+            println!("   $ {pipeline} > {}", shell_words::quote(&target));
+        } else {
+            println!("   $ {pipeline}");
+        }
+    }
+    if !dry_run.fallbacks.is_empty() {
+        let fallbacks = dry_run
+            .fallbacks
+            .iter()
+            .map(|binary| binary.display().to_string())
+            .collect::<Vec<_>>();
+        println!("   Falls back to {}", fallbacks.join(", "));
+    }
+    match &dry_run.staging {
+        Some(staging) if !dry_run.wrap => println!(
+            "   Output: {}, through {}",
+            dry_run.target.display(),
+            staging.display()
+        ),
+        Some(staging) => println!(
+            "   Output: {}, through {} and wrapped in a directory when there are several entries",
+            dry_run.target.display(),
+            staging.display()
+        ),
+        None => println!("   Output: {}", dry_run.target.display()),
+    }
+    for command in &dry_run.post_extract {
+        println!("   Then: $ {command}");
+    }
+    if !dry_run.disposed.is_empty() {
+        let files = dry_run
+            .disposed
+            .iter()
+            .map(|file| file.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        match &extract.move_to {
+            _ if extract.delete_after => println!("   Then: delete {files}"),
+            _ if extract.trash => println!("   Then: move {files} to the trash"),
+            Some(dir) => println!("   Then: move {files} into {}", dir.display()),
+            None => {}
+        }
+    }
+}
+
+fn print_extraction(extraction: &Extraction, report: Option<ReportFormat>, chatty: bool) {
+    match report {
+        Some(ReportFormat::Human) => print_report(extraction),
+        Some(ReportFormat::Json) => println!("{}", report_json(extraction)),
+        None if extraction.skipped => println!(
+            "⏭️  Skipped {}, {} already exists",
+            extraction.file.display(),
+            extraction.target.display()
+        ),
+        None if chatty => println!(
+            "✨ Extracted {} using {}",
+            extraction.file.display(),
+            extraction
+                .backend
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+        ),
+        None => {}
+    }
+}
+
+fn print_report(extraction: &Extraction) {
+    use owo_colors::OwoColorize;
+
+    let report = &extraction.report;
+    let backend = extraction
+        .backend
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy();
+
+    if extraction.skipped {
+        println!(
+            "⏭️  {} skipped, {} already exists",
+            extraction.file.display().bold(),
+            extraction.target.display()
+        );
+        return;
+    }
+
+    println!(
+        "📋 {} ({}) extracted with {} in {:.2}s",
+        extraction.file.display().bold(),
+        extraction.format,
+        backend,
+        report.elapsed.as_secs_f64()
+    );
+    println!(
+        "   {} files, {} directories, {} symlinks, {}",
+        report.files,
+        report.directories,
+        report.symlinks,
+        human_bytes(report.bytes)
+    );
+    for entry in &report.entries {
+        let suffix = if entry.is_dir() && !entry.is_symlink() {
+            "/"
+        } else {
+            ""
+        };
+        println!("   {} {}{suffix}", "+".green(), entry.display());
+    }
+}
+
+fn report_json(extraction: &Extraction) -> serde_json::Value {
+    let report = &extraction.report;
+    serde_json::json!({
+        "file": extraction.file,
+        "format": extraction.format,
+        "backend": extraction.backend,
+        "output_dir": extraction.output_dir,
+        "target": extraction.target,
+        "skipped": extraction.skipped,
+        "disposed": extraction.disposed,
+        "entries": report.entries,
+        "files": report.files,
+        "directories": report.directories,
+        "symlinks": report.symlinks,
+        "bytes": report.bytes,
+        "elapsed_ms": report.elapsed.as_millis() as u64,
+    })
+}
+
+fn human_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+fn print_config(config: &Config) {
+    use owo_colors::OwoColorize;
+
+    for (key, value, source) in config.entries() {
+        println!("{key} = {value} {}", format!("# {source}").dimmed());
+    }
+}
+
+fn print_package_info(files: &[PathBuf]) -> Result<()> {
+    use owo_colors::OwoColorize;
+
+    for (index, file) in files.iter().enumerate() {
+        let info = read_package_info(file)?;
+
+        if index > 0 {
+            println!();
+        }
+        println!("{}", file.display().bold());
+        println!("  {:<14} {}", "Name:", info.name);
+        println!("  {:<14} {}", "Version:", info.version);
+        println!("  {:<14} {}", "Architecture:", info.architecture);
+        println!("  {:<14} {}", "Dependencies:", info.dependencies.join(", "));
+        println!("  {:<14} {}", "Files:", info.files.len());
+        for path in &info.files {
+            println!("    {path}");
+        }
     }
 
     Ok(())
 }
 
-fn print_health_check() -> Result<()> {
-    use extractor::get_health_info;
+fn humanize_error(error: &Error) -> String {
+    match error {
+        Error::UnsupportedFormat { file } => format!(
+            "💥 Whoops! Unable to find a supported extractor for {}, is the file an archive?",
+            file.to_string_lossy()
+        ),
+        Error::NoToolInstalled { format, tools } => format!(
+            "💥 Whoops! No suitable extraction tool found on your system for .{format} archives. Please use your package manager to install one of: {}",
+            tools.join(", ")
+        ),
+        Error::UntrustedTool { tool, path } => format!(
+            "💥 Whoops! Found {tool} at {}, but extr only runs tools installed in trusted system directories such as /usr/bin.",
+            path.display()
+        ),
+        Error::VerificationFailed { file, problems } => format!(
+            "💥 Whoops! Verifying {} failed:\n  {}",
+            file.display(),
+            problems.join("\n  ")
+        ),
+        Error::ArchiveFailed { reason, .. } => {
+            format!("💥 Whoops! {error}. {}", reason.suggestion())
+        }
+        error => format!("💥 Whoops! {error}"),
+    }
+}
+
+/// The packages to install for formats without a usable tool, one per
+/// format, with duplicates removed.
+fn missing_packages(manager: PackageManager, health_info: &[FormatHealth]) -> Vec<&'static str> {
+    let mut packages = Vec::new();
+    for health in health_info
+        .iter()
+        .filter(|health| health.selected.is_none())
+    {
+        if let Some(package) = health
+            .binaries
+            .iter()
+            .filter(|(_, installed)| !installed)
+            .find_map(|(bin, _)| manager.package(bin))
+        {
+            if !packages.contains(&package) {
+                packages.push(package);
+            }
+        }
+    }
+    packages
+}
+
+fn print_fix_script(health_info: &[FormatHealth]) {
+    println!("#!/bin/sh");
+    println!("# Installs the tools extr is missing, generated by extr --health --fix-script");
+    println!("set -e");
+
+    let Some(manager) = PackageManager::detect() else {
+        eprintln!("⚠️  No supported package manager found, the script installs nothing");
+        println!("# No supported package manager found");
+        return;
+    };
+
+    let packages = missing_packages(manager, health_info);
+    if packages.is_empty() {
+        println!("# Nothing to install");
+    } else {
+        println!("{}", manager.install_command(&packages, true));
+    }
+}
+
+fn print_health_check(health_info: Vec<FormatHealth>) -> Result<()> {
     use owo_colors::OwoColorize;
 
-    let health_info = get_health_info();
+    let manager = PackageManager::detect();
 
     println!("{}", "Archive format support health check\n".bold());
     println!(
         "To support a format at least one of the compatible\nbinaries must be installed on the system.\n"
     );
     println!(
-        "{:<15} {:<30}",
+        "{:<15} {:<12} {:<30}",
         "Format".bold(),
+        "Uses".bold(),
         "Available Binaries".bold()
     );
-    println!("{}", "―".repeat(50));
+    println!("{}", "―".repeat(62));
 
-    for (format, binaries) in health_info {
+    for health in &health_info {
         let mut available = Vec::new();
         let mut missing = Vec::new();
 
-        for (bin, installed) in binaries {
-            if installed {
-                available.push(format!("{} {}", "✓".green(), bin));
+        for (bin, installed) in &health.binaries {
+            if *installed {
+                let label = match health.versions.get(bin) {
+                    Some(version) => format!("{bin} {version}"),
+                    None => bin.clone(),
+                };
+                available.push(format!("{} {}", "✓".green(), label));
             } else {
                 missing.push(format!("{} {}", "✘".red(), bin));
             }
         }
 
         let all_bins = [available, missing].concat().join(", ");
-        println!("{:<15} {}", format!(".{}", format).blue(), all_bins);
+        let selected = health.selected.clone().unwrap_or_else(|| "-".to_string());
+        println!(
+            "{:<15} {:<12} {}",
+            format!(".{}", health.format).blue(),
+            selected.bold(),
+            all_bins
+        );
+
+        if !health.features.is_empty() {
+            let features = health
+                .features
+                .iter()
+                .map(|(feature, tool)| match tool {
+                    Some(tool) => format!("{} {feature} ({tool})", "✓".green()),
+                    None => format!("{} {feature}", "✘".red()),
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            println!("{:<28} {}", "", features);
+        }
+
+        if let (None, Some(manager)) = (&health.selected, manager) {
+            if let Some(package) = health
+                .binaries
+                .iter()
+                .filter(|(_, installed)| !installed)
+                .find_map(|(bin, _)| manager.package(bin))
+            {
+                println!(
+                    "{:<28} install with: {}",
+                    "",
+                    manager.install_command(&[package], false)
+                );
+            }
+        }
     }
 
     println!("\n{}: installed, {}: missing", "✓".green(), "✘".red());
+
+    if let Some(manager) = manager {
+        let packages = missing_packages(manager, &health_info);
+        if !packages.is_empty() {
+            println!(
+                "\nInstall everything missing with:\n  {}\nor generate a script with $ extr --health --fix-script",
+                manager.install_command(&packages, false)
+            );
+        }
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations_with_units() {
+        assert_eq!(parse_duration("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration(" 10m "), Ok(Duration::from_secs(600)));
+        assert_eq!(parse_duration("1.5h"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("0.5"), Ok(Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn refuses_invalid_durations() {
+        for value in ["", "s", "0", "-5s", "10d", "inf", "NaN", "1e400"] {
+            assert!(parse_duration(value).is_err(), "{value}");
+        }
+    }
+}
@@ -0,0 +1,416 @@
+//! Records of exactly what came out of archives, and checking an extracted
+//! tree against them later. Manifests are written as JSON when the file name
+//! ends in `.json`, and otherwise in the format of `sha256sum`, which only
+//! holds the hashes of regular files and can be checked with
+//! `sha256sum -c` from the output directory.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use std::fmt::{self, Write as _};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Component, Path};
+
+use crate::error::{Error, Result};
+use crate::Extraction;
+
+/// The hex encoded SHA-256 of the contents of `path`.
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hex(&hasher.finalize()))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+/// An archive the manifest was made from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveRecord {
+    pub file: String,
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+    File,
+    Directory,
+    Symlink,
+}
+
+impl fmt::Display for EntryKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            EntryKind::File => "file",
+            EntryKind::Directory => "directory",
+            EntryKind::Symlink => "symlink",
+        })
+    }
+}
+
+/// One extracted path, relative to the output directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub path: String,
+    #[serde(rename = "type")]
+    pub kind: EntryKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    /// Permission bits in octal, e.g. `0644`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Where a symlink points
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+}
+
+/// Everything extracted in one run, with the hashes of the archives.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub archives: Vec<ArchiveRecord>,
+    pub entries: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    /// Records the archive of `extraction` and everything it created or
+    /// changed.
+    pub fn add(&mut self, extraction: &Extraction) -> Result<()> {
+        self.archives.push(ArchiveRecord {
+            file: extraction.file.to_string_lossy().into_owned(),
+            sha256: sha256_file(&extraction.file)?,
+        });
+
+        for path in &extraction.report.paths {
+            self.entries.push(describe(&extraction.output_dir, path)?);
+        }
+        self.entries.sort_by(|a, b| a.path.cmp(&b.path));
+        self.entries.dedup_by(|a, b| a.path == b.path);
+        Ok(())
+    }
+
+    /// Writes the manifest to `path`, as JSON for `.json` files and in the
+    /// `sha256sum` format otherwise.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let content = if is_json(path) {
+            let mut json = serde_json::to_string_pretty(self).map_err(io::Error::from)?;
+            json.push('\n');
+            json
+        } else {
+            self.to_sha256sums()
+        };
+        fs::write(path, content)?;
+        Ok(())
+    }
+
+    /// Reads a manifest written by [`Manifest::write`] or a plain
+    /// `sha256sum` listing.
+    pub fn read(path: &Path) -> Result<Manifest> {
+        let content = fs::read_to_string(path)?;
+        if is_json(path) {
+            return serde_json::from_str(&content).map_err(|error| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}: {error}", path.display()),
+                )
+                .into()
+            });
+        }
+        Ok(Manifest::from_sha256sums(&content))
+    }
+
+    fn to_sha256sums(&self) -> String {
+        let mut out = String::new();
+        for archive in &self.archives {
+            let _ = writeln!(out, "# archive {}  {}", archive.sha256, archive.file);
+        }
+        for entry in &self.entries {
+            let Some(sha256) = &entry.sha256 else {
+                continue;
+            };
+            // Names with a backslash or newline are escaped like sha256sum
+            // does, marked by a leading backslash
+            if entry.path.contains(['\\', '\n']) {
+                let escaped = entry.path.replace('\\', "\\\\").replace('\n', "\\n");
+                let _ = writeln!(out, "\\{sha256}  {escaped}");
+            } else {
+                let _ = writeln!(out, "{sha256}  {}", entry.path);
+            }
+        }
+        out
+    }
+
+    fn from_sha256sums(content: &str) -> Manifest {
+        let mut manifest = Manifest::default();
+        for line in content.lines() {
+            if let Some(archive) = line.strip_prefix("# archive ") {
+                if let Some((sha256, file)) = archive.split_once("  ") {
+                    manifest.archives.push(ArchiveRecord {
+                        file: file.to_string(),
+                        sha256: sha256.to_string(),
+                    });
+                }
+                continue;
+            }
+            if line.starts_with('#') {
+                continue;
+            }
+
+            let (escaped, line) = match line.strip_prefix('\\') {
+                Some(line) => (true, line),
+                None => (false, line),
+            };
+            // Binary mode entries have a `*` in front of the name
+            let Some((sha256, path)) = line.split_once("  ").or_else(|| line.split_once(" *"))
+            else {
+                continue;
+            };
+            let path = if escaped {
+                unescape(path)
+            } else {
+                path.to_string()
+            };
+
+            manifest.entries.push(ManifestEntry {
+                path,
+                kind: EntryKind::File,
+                size: None,
+                mode: None,
+                sha256: Some(sha256.to_lowercase()),
+                target: None,
+            });
+        }
+        manifest
+    }
+
+    /// Checks the tree in `root` against the manifest. Every recorded path
+    /// has to be there unchanged, and the directories may not hold anything
+    /// that is not recorded.
+    pub fn verify(&self, root: &Path) -> Result<Vec<String>> {
+        let mut problems = Vec::new();
+        let recorded = self
+            .entries
+            .iter()
+            .map(|entry| entry.path.as_str())
+            .collect::<BTreeSet<_>>();
+
+        for expected in &self.entries {
+            let path = root.join(safe_relative(&expected.path)?);
+            let actual = match describe(root, &path) {
+                Ok(actual) => actual,
+                Err(Error::Io(error)) if error.kind() == io::ErrorKind::NotFound => {
+                    problems.push(format!("{} is missing", expected.path));
+                    continue;
+                }
+                Err(error) => return Err(error),
+            };
+
+            if actual.kind != expected.kind {
+                problems.push(format!(
+                    "{} is a {}, expected a {}",
+                    expected.path, actual.kind, expected.kind
+                ));
+                continue;
+            }
+            for (what, expected_value, actual_value) in [
+                (
+                    "size",
+                    expected.size.map(|size| size.to_string()),
+                    actual.size.map(|size| size.to_string()),
+                ),
+                ("mode", expected.mode.clone(), actual.mode.clone()),
+                ("sha256", expected.sha256.clone(), actual.sha256.clone()),
+                ("target", expected.target.clone(), actual.target.clone()),
+            ] {
+                if let (Some(expected_value), Some(actual_value)) = (expected_value, actual_value) {
+                    if expected_value != actual_value {
+                        problems.push(format!(
+                            "{} has {what} {actual_value}, expected {expected_value}",
+                            expected.path
+                        ));
+                    }
+                }
+            }
+
+            if expected.kind == EntryKind::Directory {
+                for child in fs::read_dir(&path)? {
+                    let child = describe(root, &child?.path())?;
+                    if !recorded.contains(child.path.as_str()) {
+                        problems.push(format!("{} is not in the manifest", child.path));
+                    }
+                }
+            }
+        }
+
+        Ok(problems)
+    }
+}
+
+/// Undoes the `\\` and `\n` escapes of `sha256sum`.
+fn unescape(path: &str) -> String {
+    let mut unescaped = String::with_capacity(path.len());
+    let mut chars = path.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                unescaped.push('\n');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                unescaped.push('\\');
+                chars.next();
+            }
+            (c, _) => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+}
+
+/// Refuses manifest paths that would point outside the checked directory.
+fn safe_relative(path: &str) -> Result<&Path> {
+    let relative = Path::new(path);
+    if relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+    {
+        Ok(relative)
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("manifest path {path} points outside the directory"),
+        )
+        .into())
+    }
+}
+
+/// The manifest entry for `path` as it is on disk.
+fn describe(root: &Path, path: &Path) -> Result<ManifestEntry> {
+    let metadata = fs::symlink_metadata(path)?;
+    let relative = path
+        .strip_prefix(root)
+        .unwrap_or(path)
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+
+    let mut entry = ManifestEntry {
+        path: relative,
+        kind: EntryKind::File,
+        size: None,
+        mode: mode(&metadata),
+        sha256: None,
+        target: None,
+    };
+
+    let file_type = metadata.file_type();
+    if file_type.is_symlink() {
+        entry.kind = EntryKind::Symlink;
+        entry.mode = None;
+        entry.target = Some(fs::read_link(path)?.to_string_lossy().into_owned());
+    } else if file_type.is_dir() {
+        entry.kind = EntryKind::Directory;
+    } else {
+        entry.size = Some(metadata.len());
+        entry.sha256 = Some(sha256_file(path)?);
+    }
+    Ok(entry)
+}
+
+#[cfg(unix)]
+fn mode(metadata: &fs::Metadata) -> Option<String> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(format!("{:04o}", metadata.permissions().mode() & 0o7777))
+}
+
+#[cfg(not(unix))]
+fn mode(_metadata: &fs::Metadata) -> Option<String> {
+    None
+}
+
+/// Checks `root` against the manifest in `manifest_path`.
+pub fn verify_manifest(manifest_path: &Path, root: &Path) -> Result<usize> {
+    let manifest = Manifest::read(manifest_path)?;
+    let problems = manifest.verify(root)?;
+    if !problems.is_empty() {
+        return Err(Error::VerificationFailed {
+            file: manifest_path.to_path_buf(),
+            problems,
+        });
+    }
+    Ok(manifest.entries.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_entry(path: &str, sha256: &str) -> ManifestEntry {
+        ManifestEntry {
+            path: path.to_string(),
+            kind: EntryKind::File,
+            size: None,
+            mode: None,
+            sha256: Some(sha256.to_string()),
+            target: None,
+        }
+    }
+
+    #[test]
+    fn sha256sums_round_trip() {
+        let manifest = Manifest {
+            archives: vec![ArchiveRecord {
+                file: "release.tar.gz".to_string(),
+                sha256: "a".repeat(64),
+            }],
+            entries: vec![
+                file_entry("bin/tool", &"b".repeat(64)),
+                file_entry("with  two spaces", &"c".repeat(64)),
+                file_entry("back\\slash", &"d".repeat(64)),
+                file_entry("new\nline", &"e".repeat(64)),
+            ],
+        };
+
+        let written = manifest.to_sha256sums();
+        assert!(written.contains(&format!("\\{}  back\\\\slash\n", "d".repeat(64))));
+        assert!(written.contains(&format!("\\{}  new\\nline\n", "e".repeat(64))));
+        assert_eq!(Manifest::from_sha256sums(&written), manifest);
+    }
+
+    #[test]
+    fn reads_plain_sha256sum_output() {
+        let listing = format!("{} *data.bin\n# comment\n", "A".repeat(64));
+        assert_eq!(
+            Manifest::from_sha256sums(&listing).entries,
+            [file_entry("data.bin", &"a".repeat(64))]
+        );
+    }
+
+    #[test]
+    fn sha256sums_skip_entries_without_a_hash() {
+        let mut directory = file_entry("docs", "");
+        directory.kind = EntryKind::Directory;
+        directory.sha256 = None;
+        let manifest = Manifest {
+            archives: Vec::new(),
+            entries: vec![directory, file_entry("docs/README", &"f".repeat(64))],
+        };
+
+        assert_eq!(
+            Manifest::from_sha256sums(&manifest.to_sha256sums()).entries,
+            [file_entry("docs/README", &"f".repeat(64))]
+        );
+    }
+}
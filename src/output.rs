@@ -0,0 +1,337 @@
+//! Putting extracted contents in place: what happens when something is
+//! already in the way, wrapping loose files in a directory and the commands
+//! run once an archive has been extracted.
+
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+
+use crate::error::{Error, Result};
+use crate::extractor::compression::Compression;
+use crate::trust::{find_trusted_binary, run_command};
+
+/// What to do when the output would replace an existing file or directory.
+/// Applies to decompressed single files and to wrapped contents, archive
+/// tools writing into an existing directory keep their own behavior.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Overwrite {
+    /// Stop with an error
+    #[default]
+    Refuse,
+    /// Remove what is in the way
+    Replace,
+    /// Leave the existing output alone and skip the archive
+    Skip,
+}
+
+impl FromStr for Overwrite {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "refuse" => Ok(Overwrite::Refuse),
+            "replace" => Ok(Overwrite::Replace),
+            "skip" => Ok(Overwrite::Skip),
+            _ => Err(format!(
+                "unknown overwrite policy {value}, expected refuse, replace or skip"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Overwrite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Overwrite::Refuse => "refuse",
+            Overwrite::Replace => "replace",
+            Overwrite::Skip => "skip",
+        })
+    }
+}
+
+/// Makes room for `target` according to `overwrite`. Returns `false` when
+/// the archive should be skipped.
+pub(crate) fn clear_target(target: &Path, overwrite: Overwrite) -> Result<bool> {
+    if target.symlink_metadata().is_err() {
+        return Ok(true);
+    }
+
+    match overwrite {
+        Overwrite::Refuse => Err(Error::OutputExists {
+            path: target.to_path_buf(),
+        }),
+        Overwrite::Replace => {
+            remove_path(target)?;
+            Ok(true)
+        }
+        Overwrite::Skip => Ok(false),
+    }
+}
+
+/// Staging directory in `output_dir` that a wrapped archive is extracted
+/// into before its contents are moved in place.
+pub(crate) fn staging_dir(file: &Path, output_dir: &Path) -> PathBuf {
+    let name = file.file_name().unwrap_or_default().to_string_lossy();
+    output_dir.join(format!(".{name}.extr-partial"))
+}
+
+/// Moves the contents of `staging` into `output_dir`. A single top level
+/// entry is moved as it is, several entries end up in a directory named
+/// after the archive. Returns where the contents go and whether they were
+/// moved there, they are not when `overwrite` says to skip.
+pub(crate) fn unwrap_staging(
+    staging: &Path,
+    file: &Path,
+    format: &str,
+    output_dir: &Path,
+    overwrite: Overwrite,
+) -> Result<(PathBuf, bool)> {
+    let entries = fs::read_dir(staging)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+
+    // Never let the contents take the place of the archive itself
+    let single = match entries.as_slice() {
+        [entry] => Some(output_dir.join(entry.file_name().unwrap()))
+            .filter(|target| !is_same_file(target, file))
+            .map(|target| (entry.clone(), target)),
+        _ => None,
+    };
+
+    let (from, target) = match single {
+        Some(single) => single,
+        None if entries.is_empty() => {
+            fs::remove_dir(staging)?;
+            return Ok((output_dir.to_path_buf(), true));
+        }
+        None => {
+            let mut target = output_dir.join(wrapper_name(file, format));
+            if is_same_file(&target, file) {
+                target = target.with_file_name(format!(
+                    "{}.d",
+                    target.file_name().unwrap().to_string_lossy()
+                ));
+            }
+            (staging.to_path_buf(), target)
+        }
+    };
+
+    if !clear_target(&target, overwrite)? {
+        return Ok((target, false));
+    }
+    fs::rename(&from, &target)?;
+    Ok((target, true))
+}
+
+/// Moves everything in `staging` into `output_dir` and removes `staging`.
+/// Directories that are already there are merged and files of the same name
+/// are replaced, the way archive tools write into an existing directory.
+/// Returns the paths that were moved into place.
+pub(crate) fn merge_staging(
+    staging: &Path,
+    file: &Path,
+    output_dir: &Path,
+) -> Result<Vec<PathBuf>> {
+    let mut placed = Vec::new();
+    merge_into(staging, file, output_dir, &mut placed)?;
+    fs::remove_dir(staging)?;
+    placed.sort();
+    Ok(placed)
+}
+
+fn merge_into(from: &Path, file: &Path, to: &Path, placed: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let source = entry.path();
+        let target = to.join(entry.file_name());
+        let is_dir = entry.file_type()?.is_dir();
+
+        match fs::symlink_metadata(&target) {
+            Ok(existing) if existing.is_dir() && is_dir => {
+                merge_into(&source, file, &target, placed)?;
+                fs::remove_dir(&source)?;
+            }
+            // Never replace the archive itself or turn files into directories
+            // and back
+            Ok(existing) if existing.is_dir() || is_dir || is_same_file(&target, file) => {
+                return Err(Error::OutputExists { path: target });
+            }
+            _ => {
+                fs::rename(&source, &target)?;
+                placed.push(target);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `release-1.0.tar.gz` becomes `release-1.0`. Compression layers the
+/// format does not include, such as the `.b64` of a base64 encoded tarball,
+/// are dropped along the way.
+fn wrapper_name(file: &Path, format: &str) -> String {
+    let mut name = file
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    let suffix = format!(".{format}");
+
+    loop {
+        let lowered = name.to_lowercase();
+        if lowered.ends_with(&suffix) && lowered.len() > suffix.len() {
+            name.truncate(name.len() - suffix.len());
+            break;
+        }
+        match lowered.rsplit_once('.') {
+            Some((stem, extension))
+                if !stem.is_empty() && Compression::from_extension(extension).is_some() =>
+            {
+                name.truncate(stem.len());
+            }
+            _ => break,
+        }
+    }
+    name
+}
+
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// `path` and everything below it that can be read, parents before their
+/// children.
+pub(crate) fn tree(path: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = vec![path.to_path_buf()];
+    if fs::symlink_metadata(path)?.is_dir() {
+        walk(path, &mut |path| paths.push(path.to_path_buf()));
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Calls `visit` for everything below `dir`, descending into directories
+/// but not into symlinks to them. What cannot be read, such as a directory
+/// without permissions or on a stale network mount, is left out instead of
+/// failing the extraction that only wanted to look at it.
+fn walk(dir: &Path, visit: &mut dyn FnMut(&Path)) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(metadata) = fs::symlink_metadata(&path) else {
+            continue;
+        };
+        visit(&path);
+        if metadata.is_dir() {
+            walk(&path, visit);
+        }
+    }
+}
+
+pub(crate) fn remove_path(path: &Path) -> Result<()> {
+    if path.symlink_metadata()?.is_dir() {
+        fs::remove_dir_all(path)?;
+    } else {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Runs the post-extract `commands` for an archive, with `{file}` replaced
+/// by the archive and `{out}` by where its contents ended up. Commands are
+/// split like a shell would, but not run through one, and their program
+/// must be installed in a trusted directory. Their output is always shown.
+pub(crate) fn run_post_extract(commands: &[String], file: &Path, out: &Path) -> Result<()> {
+    for command in commands {
+        let words = post_extract_words(command, file, out)?;
+        let Some((program, args)) = words.split_first() else {
+            continue;
+        };
+
+        let binary = find_trusted_binary(program).ok_or_else(|| Error::Config {
+            path: None,
+            message: format!(
+                "post-extract command {program} is not installed in a trusted directory"
+            ),
+        })?;
+
+        let mut cmd = Command::new(binary);
+        cmd.args(args);
+        run_command(cmd, true)?;
+    }
+    Ok(())
+}
+
+/// The words of a post-extract `command` with the placeholders filled in.
+pub(crate) fn post_extract_words(command: &str, file: &Path, out: &Path) -> Result<Vec<String>> {
+    let words = shell_words::split(command).map_err(|error| Error::Config {
+        path: None,
+        message: format!("invalid post-extract command {command:?}: {error}"),
+    })?;
+    Ok(words
+        .into_iter()
+        .map(|word| {
+            word.replace("{file}", &file.to_string_lossy())
+                .replace("{out}", &out.to_string_lossy())
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrapper_names_drop_the_format_and_outer_layers() {
+        for (file, format, name) in [
+            ("dist/release-1.0.tar.gz", "tar.gz", "release-1.0"),
+            ("Photos.ZIP", "zip", "Photos"),
+            ("backup.tar.gz.b64", "tar.gz", "backup"),
+            ("image.cpio.zst", "cpio.zst", "image"),
+            (".zip", "zip", ".zip"),
+            ("notes", "zip", "notes"),
+        ] {
+            assert_eq!(wrapper_name(Path::new(file), format), name, "{file}");
+        }
+    }
+
+    #[test]
+    fn merges_staged_contents_into_existing_directories() {
+        let dir = crate::scratch_dir("output-merge");
+        let staging = dir.join(".a.tar.extr-partial");
+        fs::create_dir_all(staging.join("docs")).unwrap();
+        fs::create_dir_all(dir.join("docs")).unwrap();
+        fs::write(staging.join("docs/new"), "new").unwrap();
+        fs::write(staging.join("docs/same"), "archived").unwrap();
+        fs::write(staging.join("top"), "top").unwrap();
+        fs::write(dir.join("docs/same"), "old").unwrap();
+        fs::write(dir.join("docs/mine"), "mine").unwrap();
+
+        let placed = merge_staging(&staging, &dir.join("a.tar"), &dir).unwrap();
+        assert_eq!(
+            placed,
+            [dir.join("docs/new"), dir.join("docs/same"), dir.join("top")]
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("docs/same")).unwrap(),
+            "archived"
+        );
+        assert_eq!(fs::read_to_string(dir.join("docs/mine")).unwrap(), "mine");
+        assert!(!staging.exists());
+
+        fs::create_dir_all(staging.join("top")).unwrap();
+        assert!(matches!(
+            merge_staging(&staging, &dir.join("a.tar"), &dir),
+            Err(Error::OutputExists { .. })
+        ));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,370 @@
+//! Metadata of `.deb` and `.rpm` packages.
+//!
+//! Package extraction writes the payload to the output directory and the
+//! control data (control file, maintainer scripts, header fields and
+//! scriptlets) to [`META_DIR`] inside it.
+
+use std::fs::{self, File};
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::process::Command;
+
+use crate::error::{Error, Result};
+use crate::extractor::get_extension;
+use crate::extractor::pkg::{self, find_member, is_ar, require_binary, tar_from_stdin, tar_stdin};
+use crate::trust::{capture_pipeline, run_pipeline, Pipeline};
+
+/// Directory inside the output directory that receives package metadata.
+pub const META_DIR: &str = ".pkg-meta";
+
+/// What `extr --info` shows about a package.
+#[derive(Debug, Clone, Default)]
+pub struct PackageInfo {
+    pub name: String,
+    pub version: String,
+    pub architecture: String,
+    pub dependencies: Vec<String>,
+    pub files: Vec<String>,
+}
+
+/// Reads the metadata of a `.deb`, `.ipk` or `.rpm` package without
+/// extracting it.
+pub fn read_package_info(file: &Path) -> Result<PackageInfo> {
+    match get_extension(file).as_deref() {
+        Some("deb") | Some("ipk") => read_control_info(file),
+        Some("rpm") => read_rpm_info(file),
+        _ => Err(Error::UnsupportedFormat {
+            file: file.to_path_buf(),
+        }),
+    }
+}
+
+/// Unpacks the control tarball of a `.deb` or `.ipk` into
+/// `output_dir/.pkg-meta`.
+pub fn write_control_meta(file: &Path, output_dir: &Path, verbose: bool) -> Result<()> {
+    let meta_dir = output_dir.join(META_DIR);
+    fs::create_dir_all(&meta_dir)?;
+
+    let (cmd, member) = member_command(file, "control.tar")?;
+    run_pipeline(
+        Pipeline::new(cmd).pipe(tar_from_stdin(&member, &meta_dir, verbose)?),
+        verbose,
+    )
+}
+
+/// Writes the header fields and scriptlets of an `.rpm` into
+/// `output_dir/.pkg-meta`.
+pub fn write_rpm_meta(file: &Path, output_dir: &Path) -> Result<()> {
+    let header = RpmHeader::read(file)?;
+    let meta_dir = output_dir.join(META_DIR);
+    fs::create_dir_all(&meta_dir)?;
+
+    let mut fields = String::new();
+    for (label, tag) in [
+        ("Name", TAG_NAME),
+        ("Version", TAG_VERSION),
+        ("Release", TAG_RELEASE),
+        ("Architecture", TAG_ARCH),
+        ("Summary", TAG_SUMMARY),
+        ("License", TAG_LICENSE),
+        ("URL", TAG_URL),
+    ] {
+        if let Some(value) = header.string(tag) {
+            fields.push_str(&format!("{label}: {value}\n"));
+        }
+    }
+    let requires = header.strings(TAG_REQUIRENAME);
+    if !requires.is_empty() {
+        fields.push_str(&format!("Requires: {}\n", requires.join(", ")));
+    }
+    if let Some(description) = header.string(TAG_DESCRIPTION) {
+        fields.push_str(&format!("Description:\n{description}\n"));
+    }
+    fs::write(meta_dir.join("header"), fields)?;
+
+    for (name, tag) in SCRIPTLETS {
+        if let Some(script) = header.string(*tag) {
+            fs::write(meta_dir.join(name), script)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Moves the metadata that Alpine `.apk` and Arch Linux packages keep at the
+/// root of their payload, such as `.PKGINFO`, into `output_dir/.pkg-meta`.
+pub fn move_root_meta(output_dir: &Path) -> Result<()> {
+    const NAMES: &[&str] = &[
+        ".PKGINFO",
+        ".BUILDINFO",
+        ".MTREE",
+        ".INSTALL",
+        ".CHANGELOG",
+        ".pre-install",
+        ".post-install",
+        ".pre-upgrade",
+        ".post-upgrade",
+        ".pre-deinstall",
+        ".post-deinstall",
+        ".trigger",
+    ];
+
+    let meta_dir = output_dir.join(META_DIR);
+    for entry in fs::read_dir(output_dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if NAMES.contains(&name.as_ref()) || name.starts_with(".SIGN.") {
+            fs::create_dir_all(&meta_dir)?;
+            fs::rename(entry.path(), meta_dir.join(entry.file_name()))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn read_control_info(file: &Path) -> Result<PackageInfo> {
+    let (cmd, control_member) = member_command(file, "control.tar")?;
+    let mut list = tar_stdin(&control_member)?;
+    list.arg("-t");
+    let listing = capture_pipeline(Pipeline::new(cmd).pipe(list))?;
+    let control_path = String::from_utf8_lossy(&listing)
+        .lines()
+        .find(|path| path.trim_start_matches("./") == "control")
+        .map(str::to_string)
+        .ok_or_else(|| Error::UnsupportedFormat {
+            file: file.to_path_buf(),
+        })?;
+
+    let (cmd, _) = member_command(file, "control.tar")?;
+    let mut print = tar_stdin(&control_member)?;
+    print.arg("-x").arg("-O").arg(&control_path);
+    let control = capture_pipeline(Pipeline::new(cmd).pipe(print))?;
+    let control = String::from_utf8_lossy(&control);
+
+    let field = |name: &str| {
+        control
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+            .map(|value| value.trim().to_string())
+            .unwrap_or_default()
+    };
+
+    let (cmd, data_member) = member_command(file, "data.tar")?;
+    let mut list = tar_stdin(&data_member)?;
+    list.arg("-t");
+    let files = capture_pipeline(Pipeline::new(cmd).pipe(list))?;
+
+    Ok(PackageInfo {
+        name: field("Package"),
+        version: field("Version"),
+        architecture: field("Architecture"),
+        dependencies: field("Depends")
+            .split(',')
+            .map(|dependency| dependency.trim().to_string())
+            .filter(|dependency| !dependency.is_empty())
+            .collect(),
+        files: String::from_utf8_lossy(&files)
+            .lines()
+            .map(|path| path.trim_start_matches('.').to_string())
+            .filter(|path| !path.is_empty() && !path.ends_with('/'))
+            .collect(),
+    })
+}
+
+fn read_rpm_info(file: &Path) -> Result<PackageInfo> {
+    let header = RpmHeader::read(file)?;
+
+    let mut version = header.string(TAG_VERSION).unwrap_or_default();
+    if let Some(release) = header.string(TAG_RELEASE) {
+        version = format!("{version}-{release}");
+    }
+
+    let dirnames = header.strings(TAG_DIRNAMES);
+    let files = header
+        .strings(TAG_BASENAMES)
+        .into_iter()
+        .zip(header.int32s(TAG_DIRINDEXES))
+        .map(|(basename, index)| {
+            let dirname = dirnames.get(index as usize).map(String::as_str);
+            format!("{}{basename}", dirname.unwrap_or_default())
+        })
+        .chain(header.strings(TAG_OLDFILENAMES))
+        .collect();
+
+    Ok(PackageInfo {
+        name: header.string(TAG_NAME).unwrap_or_default(),
+        version,
+        architecture: header.string(TAG_ARCH).unwrap_or_default(),
+        dependencies: header
+            .strings(TAG_REQUIRENAME)
+            .into_iter()
+            .filter(|name| !name.starts_with("rpmlib("))
+            .collect(),
+        files,
+    })
+}
+
+/// A command writing the `control.tar` or `data.tar` member of the `.deb`
+/// or `.ipk` to stdout, along with the member name that tells how it is
+/// compressed.
+fn member_command(file: &Path, prefix: &str) -> Result<(Command, String)> {
+    let tools: &[&str] = if is_ar(file)? {
+        &["ar", "bsdtar"]
+    } else {
+        &["bsdtar", "tar", "gtar"]
+    };
+
+    let format = get_extension(file).unwrap_or_default();
+    if let Ok(binary) = require_binary(&format, tools) {
+        let member = find_member(file, prefix)?;
+        return Ok((pkg::member_command(&binary, file, &member), member));
+    }
+
+    // dpkg-deb hands out the members as uncompressed tarballs
+    let mut tools = tools.to_vec();
+    tools.push("dpkg-deb");
+    let mut cmd = Command::new(require_binary(&format, &tools)?);
+    if prefix == "control.tar" {
+        cmd.arg("--ctrl-tarfile");
+    } else {
+        cmd.arg("--fsys-tarfile");
+    }
+    cmd.arg(file);
+    Ok((cmd, prefix.to_string()))
+}
+
+const TAG_NAME: u32 = 1000;
+const TAG_VERSION: u32 = 1001;
+const TAG_RELEASE: u32 = 1002;
+const TAG_SUMMARY: u32 = 1004;
+const TAG_DESCRIPTION: u32 = 1005;
+const TAG_LICENSE: u32 = 1014;
+const TAG_URL: u32 = 1020;
+const TAG_ARCH: u32 = 1022;
+const TAG_OLDFILENAMES: u32 = 1027;
+const TAG_REQUIRENAME: u32 = 1049;
+const TAG_DIRINDEXES: u32 = 1116;
+const TAG_BASENAMES: u32 = 1117;
+const TAG_DIRNAMES: u32 = 1118;
+
+const SCRIPTLETS: &[(&str, u32)] = &[
+    ("preinstall", 1023),
+    ("postinstall", 1024),
+    ("preuninstall", 1025),
+    ("postuninstall", 1026),
+    ("pretrans", 1151),
+    ("posttrans", 1152),
+];
+
+const TYPE_INT32: u32 = 4;
+const TYPE_STRING: u32 = 6;
+const TYPE_STRING_ARRAY: u32 = 8;
+const TYPE_I18NSTRING: u32 = 9;
+
+/// The main header of an `.rpm` package, see the RPM file format
+/// documentation for the layout of the lead and header structures.
+struct RpmHeader {
+    /// Tag, type, offset and count of every index entry
+    entries: Vec<(u32, u32, usize, usize)>,
+    store: Vec<u8>,
+}
+
+impl RpmHeader {
+    fn read(file: &Path) -> Result<RpmHeader> {
+        let invalid = || Error::UnsupportedFormat {
+            file: file.to_path_buf(),
+        };
+        let file_size = std::fs::metadata(file)?.len();
+        let mut reader = BufReader::new(File::open(file)?);
+
+        let mut lead = [0u8; 96];
+        reader.read_exact(&mut lead)?;
+        if lead[..4] != [0xed, 0xab, 0xee, 0xdb] {
+            return Err(invalid());
+        }
+
+        // The signature header is padded to a multiple of 8 bytes
+        let signature = RpmHeader::read_structure(&mut reader, file_size).ok_or_else(invalid)?;
+        let signature_size = 16 + 16 * signature.entries.len() + signature.store.len();
+        let padding = (8 - signature_size % 8) % 8;
+        reader.read_exact(&mut vec![0u8; padding])?;
+
+        RpmHeader::read_structure(&mut reader, file_size).ok_or_else(invalid)
+    }
+
+    /// Reads a header structure, which cannot be larger than the
+    /// `file_size` of the package it is in.
+    fn read_structure(reader: &mut impl Read, file_size: u64) -> Option<RpmHeader> {
+        let mut intro = [0u8; 16];
+        reader.read_exact(&mut intro).ok()?;
+        if intro[..3] != [0x8e, 0xad, 0xe8] {
+            return None;
+        }
+        let count = u32::from_be_bytes(intro[8..12].try_into().ok()?) as usize;
+        let store_size = u32::from_be_bytes(intro[12..16].try_into().ok()?) as usize;
+        // The sizes are not trusted with an allocation before they are checked
+        let size = count.checked_mul(16)?.checked_add(store_size)?;
+        if size as u64 > file_size {
+            return None;
+        }
+
+        let mut index = vec![0u8; count * 16];
+        reader.read_exact(&mut index).ok()?;
+        let entries = index
+            .chunks_exact(16)
+            .map(|entry| {
+                let field = |at: usize| u32::from_be_bytes(entry[at..at + 4].try_into().unwrap());
+                (field(0), field(4), field(8) as usize, field(12) as usize)
+            })
+            .collect();
+
+        let mut store = vec![0u8; store_size];
+        reader.read_exact(&mut store).ok()?;
+
+        Some(RpmHeader { entries, store })
+    }
+
+    fn entry(&self, tag: u32) -> Option<(u32, usize, usize)> {
+        self.entries
+            .iter()
+            .find(|(entry_tag, ..)| *entry_tag == tag)
+            .map(|(_, kind, offset, count)| (*kind, *offset, *count))
+    }
+
+    fn string(&self, tag: u32) -> Option<String> {
+        self.strings(tag).into_iter().next()
+    }
+
+    fn strings(&self, tag: u32) -> Vec<String> {
+        let Some((kind, offset, count)) = self.entry(tag) else {
+            return Vec::new();
+        };
+        if ![TYPE_STRING, TYPE_STRING_ARRAY, TYPE_I18NSTRING].contains(&kind) {
+            return Vec::new();
+        }
+        let count = if kind == TYPE_STRING { 1 } else { count };
+
+        self.store
+            .get(offset..)
+            .unwrap_or_default()
+            .split(|byte| *byte == 0)
+            .take(count)
+            .map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+            .collect()
+    }
+
+    fn int32s(&self, tag: u32) -> Vec<u32> {
+        match self.entry(tag) {
+            Some((TYPE_INT32, offset, count)) => self
+                .store
+                .get(offset..)
+                .unwrap_or_default()
+                .chunks_exact(4)
+                .take(count)
+                .map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
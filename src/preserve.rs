@@ -0,0 +1,353 @@
+//! Control over the ownership, permissions and timestamps of extracted
+//! files. Left alone, every tool does its own thing: `tar` run as root
+//! restores ownership, `unzip` restores mode bits and `7z` often drops them.
+//! The flags of the tools that have them are set up front, a pass over
+//! every extracted file afterwards takes care of the rest.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::error::Result;
+use crate::probe::{probe_tool, Flavor};
+
+/// A file attribute the archive can carry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Attribute {
+    /// Permission bits
+    Mode,
+    /// User and group
+    Owner,
+    /// Modification time
+    Mtime,
+    /// Extended attributes in the `user` namespace
+    Xattrs,
+    /// POSIX access control lists
+    Acl,
+}
+
+impl Attribute {
+    pub const ALL: [Attribute; 5] = [
+        Attribute::Mode,
+        Attribute::Owner,
+        Attribute::Mtime,
+        Attribute::Xattrs,
+        Attribute::Acl,
+    ];
+}
+
+impl FromStr for Attribute {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        Attribute::ALL
+            .into_iter()
+            .find(|attribute| attribute.to_string() == value.trim().to_lowercase())
+            .ok_or_else(|| {
+                format!("unknown attribute {value}, expected mode, owner, mtime, xattrs or acl")
+            })
+    }
+}
+
+impl fmt::Display for Attribute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Attribute::Mode => "mode",
+            Attribute::Owner => "owner",
+            Attribute::Mtime => "mtime",
+            Attribute::Xattrs => "xattrs",
+            Attribute::Acl => "acl",
+        })
+    }
+}
+
+/// Parses a comma separated list of attributes such as `mode,mtime`, or
+/// `all` or `none`.
+pub fn parse_attributes(list: &str) -> std::result::Result<BTreeSet<Attribute>, String> {
+    match list.trim().to_lowercase().as_str() {
+        "all" => Ok(Attribute::ALL.into_iter().collect()),
+        "none" | "" => Ok(BTreeSet::new()),
+        _ => list.split(',').map(str::parse).collect(),
+    }
+}
+
+/// What to do with the attributes stored in archives.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Preservation {
+    /// Attributes to restore from the archive. For the others, files are
+    /// owned by the current user, get the current time, have their mode
+    /// masked by the umask and lose their extended attributes and ACLs.
+    /// `None` leaves it to each tool.
+    pub preserve: Option<BTreeSet<Attribute>>,
+    /// Strip setuid and setgid bits and write permission for others
+    pub normalize: bool,
+}
+
+impl Preservation {
+    /// Whether extracting is left entirely to the tools.
+    pub fn is_default(&self) -> bool {
+        self.preserve.is_none() && !self.normalize
+    }
+
+    fn keeps(&self, attribute: Attribute) -> bool {
+        self.preserve
+            .as_ref()
+            .is_some_and(|preserve| preserve.contains(&attribute))
+    }
+
+    /// Options for `tool` matching the requested attributes. They go in
+    /// front of the other arguments, where every supported tool expects its
+    /// options.
+    pub fn tool_args(&self, tool: &str) -> Vec<&'static str> {
+        if self.preserve.is_none() {
+            return Vec::new();
+        }
+
+        let pick = |attribute, keep: &'static [&'static str], drop: &'static [&'static str]| {
+            if self.keeps(attribute) {
+                keep
+            } else {
+                drop
+            }
+        };
+
+        let args: Vec<&[&str]> = match tool {
+            "tar" | "gtar" | "bsdtar" => {
+                let gnu = probe_tool(tool).is_some_and(|info| info.flavor == Flavor::GnuTar);
+                vec![
+                    pick(
+                        Attribute::Mode,
+                        &["--preserve-permissions"],
+                        &["--no-same-permissions"],
+                    ),
+                    pick(Attribute::Owner, &["--same-owner"], &["--no-same-owner"]),
+                    pick(Attribute::Mtime, &[], &["-m"]),
+                    // GNU tar needs to be told which namespaces to restore
+                    if gnu {
+                        pick(
+                            Attribute::Xattrs,
+                            &["--xattrs", "--xattrs-include=user.*"],
+                            &["--no-xattrs"],
+                        )
+                    } else {
+                        pick(Attribute::Xattrs, &["--xattrs"], &["--no-xattrs"])
+                    },
+                    pick(Attribute::Acl, &["--acls"], &["--no-acls"]),
+                ]
+            }
+            "unzip" => vec![
+                pick(Attribute::Owner, &["-X"], &[]),
+                pick(Attribute::Mtime, &[], &["-DD"]),
+            ],
+            "cpio" => vec![pick(Attribute::Owner, &[], &["--no-preserve-owner"])],
+            "unsquashfs" => vec![pick(Attribute::Xattrs, &["-xattrs"], &["-no-xattrs"])],
+            _ => Vec::new(),
+        };
+
+        args.concat()
+    }
+
+    /// Enforces the settings on the extracted `path`, but not on what is in
+    /// it, for tools without the flags and for what the flags do not cover.
+    pub fn apply(&self, path: &Path) -> Result<()> {
+        if self.is_default() {
+            return Ok(());
+        }
+        self.apply_path(path)
+    }
+
+    #[cfg(unix)]
+    fn apply_path(&self, path: &Path) -> Result<()> {
+        unix::apply(self, path, unix::umask())
+    }
+
+    #[cfg(not(unix))]
+    fn apply_path(&self, _path: &Path) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+mod unix {
+    use super::{Attribute, Preservation};
+    use crate::error::Result;
+    use filetime::FileTime;
+    use std::fs;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+    use std::path::Path;
+
+    const ACL_ATTRIBUTES: &[&str] = &["system.posix_acl_access", "system.posix_acl_default"];
+
+    /// Filesystems without extended attributes have none to remove.
+    fn supported<T>(result: std::io::Result<T>) -> std::io::Result<Option<T>> {
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(error) if error.raw_os_error() == Some(libc::ENOTSUP) => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    pub fn umask() -> u32 {
+        // There is no way to read the umask without setting it
+        unsafe {
+            let mask = libc::umask(0o022);
+            libc::umask(mask);
+            mask as u32
+        }
+    }
+
+    pub fn apply(preservation: &Preservation, path: &Path, umask: u32) -> Result<()> {
+        let metadata = fs::symlink_metadata(path)?;
+
+        if let Some(preserve) = &preservation.preserve {
+            if !preserve.contains(&Attribute::Owner) {
+                let (uid, gid) = unsafe { (libc::geteuid(), libc::getegid()) };
+                if metadata.uid() != uid || metadata.gid() != gid {
+                    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes())
+                        .map_err(std::io::Error::from)?;
+                    if unsafe { libc::lchown(c_path.as_ptr(), uid, gid) } != 0 {
+                        return Err(std::io::Error::last_os_error().into());
+                    }
+                }
+            }
+        }
+
+        // Symlinks have no permissions, timestamps are left to the target
+        if metadata.file_type().is_symlink() {
+            return Ok(());
+        }
+
+        let mut mode = metadata.mode() & 0o7777;
+        if preservation.preserve.is_some() && !preservation.keeps(Attribute::Mode) {
+            mode &= !umask;
+        }
+        if preservation.normalize {
+            // setuid, setgid and write for others
+            mode &= !0o6002;
+        }
+        if mode != metadata.mode() & 0o7777 {
+            fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+        }
+
+        if preservation.preserve.is_some() {
+            if !preservation.keeps(Attribute::Xattrs) {
+                for name in supported(xattr::list(path))?.into_iter().flatten() {
+                    if name.as_bytes().starts_with(b"user.") {
+                        xattr::remove(path, &name)?;
+                    }
+                }
+            }
+            if !preservation.keeps(Attribute::Acl) {
+                for name in ACL_ATTRIBUTES {
+                    if supported(xattr::get(path, name))?.flatten().is_some() {
+                        xattr::remove(path, name)?;
+                    }
+                }
+            }
+            if !preservation.keeps(Attribute::Mtime) {
+                filetime::set_file_mtime(path, FileTime::now())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preserving(list: &str) -> Preservation {
+        Preservation {
+            preserve: Some(parse_attributes(list).unwrap()),
+            normalize: false,
+        }
+    }
+
+    #[test]
+    fn parses_attribute_lists() {
+        assert_eq!(
+            parse_attributes(" Mode,mtime "),
+            Ok([Attribute::Mode, Attribute::Mtime].into_iter().collect())
+        );
+        assert_eq!(parse_attributes("all").unwrap().len(), Attribute::ALL.len());
+        assert_eq!(parse_attributes("none"), Ok(BTreeSet::new()));
+        assert_eq!(parse_attributes(""), Ok(BTreeSet::new()));
+        assert!(parse_attributes("mode,color").is_err());
+    }
+
+    #[test]
+    fn maps_attributes_to_tool_flags() {
+        assert_eq!(Preservation::default().tool_args("tar"), Vec::<&str>::new());
+
+        let all = preserving("all").tool_args("tar");
+        for flag in [
+            "--preserve-permissions",
+            "--same-owner",
+            "--xattrs",
+            "--acls",
+        ] {
+            assert!(all.contains(&flag), "{flag} in {all:?}");
+        }
+        assert!(!all.contains(&"-m"));
+
+        let none = preserving("none").tool_args("tar");
+        for flag in [
+            "--no-same-permissions",
+            "--no-same-owner",
+            "-m",
+            "--no-xattrs",
+        ] {
+            assert!(none.contains(&flag), "{flag} in {none:?}");
+        }
+
+        assert_eq!(preserving("owner").tool_args("unzip"), ["-X", "-DD"]);
+        assert_eq!(preserving("mtime").tool_args("unzip"), Vec::<&str>::new());
+        assert_eq!(
+            preserving("mode").tool_args("cpio"),
+            ["--no-preserve-owner"]
+        );
+        assert_eq!(preserving("owner").tool_args("cpio"), Vec::<&str>::new());
+        assert_eq!(preserving("all").tool_args("7z"), Vec::<&str>::new());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn masks_modes_and_strips_dangerous_bits() {
+        use filetime::FileTime;
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = crate::scratch_dir("preserve-apply");
+        let file = dir.join("tool");
+        let mode_after = |preservation: &Preservation, mode: u32| {
+            fs::write(&file, "").unwrap();
+            fs::set_permissions(&file, fs::Permissions::from_mode(mode)).unwrap();
+            unix::apply(preservation, &file, 0o027).unwrap();
+            fs::metadata(&file).unwrap().permissions().mode() & 0o7777
+        };
+
+        assert_eq!(mode_after(&preserving("owner"), 0o777), 0o750);
+        assert_eq!(mode_after(&preserving("owner,mode"), 0o777), 0o777);
+
+        let normalize = Preservation {
+            preserve: None,
+            normalize: true,
+        };
+        assert_eq!(mode_after(&normalize, 0o6777), 0o775);
+        assert_eq!(mode_after(&normalize, 0o640), 0o640);
+
+        let old = FileTime::from_unix_time(1_000_000_000, 0);
+        filetime::set_file_mtime(&file, old).unwrap();
+        unix::apply(&preserving("owner,mode,mtime"), &file, 0o022).unwrap();
+        assert_eq!(
+            FileTime::from_last_modification_time(&fs::metadata(&file).unwrap()),
+            old
+        );
+        unix::apply(&preserving("owner,mode"), &file, 0o022).unwrap();
+        assert!(FileTime::from_last_modification_time(&fs::metadata(&file).unwrap()) > old);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,359 @@
+//! Finding out what the installed tools can actually do. Two tools with the
+//! same name can differ a lot: an old GNU tar has no `--zstd`, `7za` can not
+//! open RAR archives and `unrar-free` does not understand RAR5.
+
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::Mutex;
+
+use crate::trust::{find_trusted_binary, probe_command};
+
+/// Parts of a format that not every tool handles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Feature {
+    /// Password protected entries
+    Encryption,
+    /// AES encrypted zip entries, rather than the traditional zip crypto
+    Aes,
+    /// The deflate64 compression method in zip archives
+    Deflate64,
+    /// Archives split over several files
+    MultiVolume,
+    /// The RAR 5.0 archive format
+    Rar5,
+    /// zstd compressed tarballs
+    Zstd,
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Feature::Encryption => "encryption",
+            Feature::Aes => "aes",
+            Feature::Deflate64 => "deflate64",
+            Feature::MultiVolume => "multi-volume",
+            Feature::Rar5 => "rar5",
+            Feature::Zstd => "zstd",
+        })
+    }
+}
+
+/// The features worth checking for `format`.
+pub fn format_features(format: &str) -> &'static [Feature] {
+    match format {
+        "zip" | "jar" | "war" => &[
+            Feature::Encryption,
+            Feature::Aes,
+            Feature::Deflate64,
+            Feature::MultiVolume,
+        ],
+        "rar" => &[Feature::Encryption, Feature::MultiVolume, Feature::Rar5],
+        "7z" => &[Feature::Encryption, Feature::MultiVolume],
+        "tar.zst" | "tzst" | "pkg.tar.zst" => &[Feature::Zstd],
+        _ => &[],
+    }
+}
+
+/// Implementations that share a binary name but not their abilities.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flavor {
+    GnuTar,
+    Bsdtar,
+    InfoZip,
+    /// `7z` and `7zz`, which open every format they have a codec for
+    SevenZip,
+    /// `7za`, which only knows the common formats
+    SevenZipStandalone,
+    /// `7zr`, which only opens `.7z` archives
+    SevenZipReduced,
+    GnuCpio,
+    /// libarchive's cpio, installed as `cpio` on the BSDs and macOS
+    Bsdcpio,
+    Unrar,
+    UnrarFree,
+    Unar,
+    Other,
+}
+
+/// What was learned about an installed tool.
+#[derive(Debug, Clone)]
+pub struct ToolInfo {
+    pub name: String,
+    pub path: PathBuf,
+    pub flavor: Flavor,
+    /// Version as printed by the tool, e.g. `1.34` or `16.02`
+    pub version: Option<String>,
+    /// Formats listed by tools that can report them, such as `7z i`
+    formats: Vec<String>,
+}
+
+impl ToolInfo {
+    /// Whether this tool handles `feature`, going by its flavor and version.
+    pub fn supports(&self, feature: Feature) -> bool {
+        let at_least = |minimum: &[u32]| {
+            self.version
+                .as_deref()
+                .is_some_and(|version| parse_version(version).as_slice() >= minimum)
+        };
+
+        match (self.flavor, feature) {
+            (Flavor::GnuTar, Feature::Zstd) => at_least(&[1, 31]),
+            (Flavor::Bsdtar, Feature::Zstd) => at_least(&[3, 3, 3]),
+            (Flavor::Bsdtar, Feature::Encryption | Feature::Aes) => at_least(&[3, 2]),
+            (Flavor::Bsdtar, Feature::Rar5) => at_least(&[3, 4]),
+            (Flavor::InfoZip, Feature::Encryption) => true,
+            (Flavor::SevenZip, Feature::Rar5) => self.lists_format("rar5"),
+            (
+                Flavor::SevenZip | Flavor::SevenZipStandalone,
+                Feature::Encryption | Feature::Aes | Feature::Deflate64 | Feature::MultiVolume,
+            ) => true,
+            (Flavor::SevenZipReduced, Feature::Encryption | Feature::MultiVolume) => true,
+            (Flavor::Unrar, Feature::Rar5) => at_least(&[5]),
+            (Flavor::Unrar, Feature::Encryption | Feature::MultiVolume) => true,
+            (Flavor::Unar, Feature::Rar5) => at_least(&[1, 10]),
+            (
+                Flavor::Unar,
+                Feature::Encryption | Feature::Aes | Feature::Deflate64 | Feature::MultiVolume,
+            ) => true,
+            _ => false,
+        }
+    }
+
+    /// Whether this tool can open `format` at all. Only `7z` builds without
+    /// the RAR codec are known not to open a format they are listed for.
+    pub fn opens(&self, format: &str) -> bool {
+        match (self.flavor, format) {
+            (Flavor::SevenZip, "rar") => self.lists_format("rar"),
+            (Flavor::SevenZipStandalone | Flavor::SevenZipReduced, "rar") => false,
+            _ => true,
+        }
+    }
+
+    fn lists_format(&self, format: &str) -> bool {
+        // Before anything was listed, give the tool the benefit of the doubt
+        self.formats.is_empty() || self.formats.iter().any(|listed| listed == format)
+    }
+}
+
+lazy_static! {
+    static ref PROBED: Mutex<HashMap<String, Option<ToolInfo>>> = Mutex::new(HashMap::new());
+}
+
+/// Runs the installed, trusted tool `name` to find out its version and
+/// flavor. Results are cached, `None` means it is not installed.
+pub fn probe_tool(name: &str) -> Option<ToolInfo> {
+    if let Some(info) = PROBED.lock().unwrap().get(name) {
+        return info.clone();
+    }
+
+    let info = find_trusted_binary(name).map(|path| {
+        let mut cmd = Command::new(&path);
+        cmd.args(version_args(name));
+        let banner = probe_command(cmd).unwrap_or_default();
+        let flavor = flavor(name, &banner);
+
+        ToolInfo {
+            name: name.to_string(),
+            path,
+            flavor,
+            version: find_version(&banner, flavor),
+            formats: listed_formats(&banner, flavor),
+        }
+    });
+
+    PROBED
+        .lock()
+        .unwrap()
+        .insert(name.to_string(), info.clone());
+    info
+}
+
+/// Arguments that make `name` print its version, for most tools that is
+/// `--version`.
+fn version_args(name: &str) -> &'static [&'static str] {
+    match name {
+        // The `i` command prints the banner followed by the supported formats
+        "7z" | "7za" | "7zr" | "7zz" => &["i"],
+        "unzip" => &["-v"],
+        "unar" => &["-v"],
+        // Prints its banner along with the usage
+        "unrar" | "lha" | "arj" => &[],
+        _ => &["--version"],
+    }
+}
+
+fn flavor(name: &str, banner: &str) -> Flavor {
+    let lowered = banner.to_lowercase();
+    match name {
+        "tar" | "gtar" if lowered.contains("gnu tar") => Flavor::GnuTar,
+        "tar" | "gtar" | "bsdtar" if lowered.contains("libarchive") => Flavor::Bsdtar,
+        "unzip" => Flavor::InfoZip,
+        "cpio" if lowered.contains("gnu cpio") => Flavor::GnuCpio,
+        "cpio" | "bsdcpio" if lowered.contains("libarchive") => Flavor::Bsdcpio,
+        "7z" | "7zz" => Flavor::SevenZip,
+        "7za" => Flavor::SevenZipStandalone,
+        "7zr" => Flavor::SevenZipReduced,
+        "unrar" if lowered.contains("unrar-free") || lowered.contains("unrar_free") => {
+            Flavor::UnrarFree
+        }
+        "unrar" => Flavor::Unrar,
+        "unar" => Flavor::Unar,
+        _ => Flavor::Other,
+    }
+}
+
+/// The first dotted version number in the banner, e.g. `1.34` from
+/// `tar (GNU tar) 1.34`. bsdtar reports the libarchive version, which is
+/// what decides its abilities.
+fn find_version(banner: &str, flavor: Flavor) -> Option<String> {
+    let text = match (flavor, banner.find("libarchive ")) {
+        (Flavor::Bsdtar, Some(start)) => &banner[start..],
+        _ => banner,
+    };
+
+    text.lines().take(5).find_map(|line| {
+        line.split(|c: char| c.is_whitespace() || c == ',' || c == '(' || c == ')')
+            .map(|word| word.trim_start_matches(|c: char| c.is_ascii_alphabetic()))
+            .find(|word| {
+                word.contains('.')
+                    && word
+                        .split('.')
+                        .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+            })
+            .map(str::to_string)
+    })
+}
+
+/// The lowercased format names in the `Formats:` table of `7z i`.
+fn listed_formats(banner: &str, flavor: Flavor) -> Vec<String> {
+    if !matches!(
+        flavor,
+        Flavor::SevenZip | Flavor::SevenZipStandalone | Flavor::SevenZipReduced
+    ) {
+        return Vec::new();
+    }
+
+    banner
+        .lines()
+        .skip_while(|line| !line.starts_with("Formats:"))
+        .skip(1)
+        .take_while(|line| !line.trim().is_empty())
+        .flat_map(|line| line.split_whitespace())
+        .filter(|word| word.chars().any(|c| c.is_ascii_alphabetic()))
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn parse_version(version: &str) -> Vec<u32> {
+    version
+        .split('.')
+        .map(|part| part.parse().unwrap_or(0))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEVEN_ZIP: &str = "\
+7-Zip [64] 16.02 : Copyright (c) 1999-2016 Igor Pavlov : 2016-05-21
+p7zip Version 16.02 (locale=C,Utf16=off,HugeFiles=on,64 bits,4 CPUs)
+
+Formats:
+ 0 C...F..........c.a.m+..  7z       7z            7z '
+ 0 ......................   Rar      rar r00       Rar!
+ 0 ......................   Rar5     rar r00       Rar!
+
+Codecs:
+ 0 4ED   303011B BCJ2
+";
+
+    fn tool(flavor: Flavor, version: &str, formats: &[&str]) -> ToolInfo {
+        ToolInfo {
+            name: String::new(),
+            path: PathBuf::new(),
+            flavor,
+            version: Some(version.to_string()),
+            formats: formats.iter().map(|format| format.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn reads_versions_from_banners() {
+        for (name, banner, version) in [
+            (
+                "tar",
+                "tar (GNU tar) 1.34\nCopyright (C) 2021",
+                Some("1.34"),
+            ),
+            (
+                "bsdtar",
+                "bsdtar 2.8.3 - libarchive 3.7.2 zlib/1.2.13 liblzma/5.4.1",
+                Some("3.7.2"),
+            ),
+            (
+                "unzip",
+                "UnZip 6.00 of 20 April 2009, by Debian.",
+                Some("6.00"),
+            ),
+            ("7z", SEVEN_ZIP, Some("16.02")),
+            ("unar", "v1.10.7", Some("1.10.7")),
+            (
+                "lha",
+                "usage: lha [-]<commands>[<options>] archive_file",
+                None,
+            ),
+        ] {
+            let flavor = flavor(name, banner);
+            assert_eq!(find_version(banner, flavor).as_deref(), version, "{name}");
+        }
+    }
+
+    #[test]
+    fn tells_flavors_apart() {
+        assert_eq!(flavor("tar", "tar (GNU tar) 1.34"), Flavor::GnuTar);
+        assert_eq!(
+            flavor("tar", "bsdtar 3.5.1 - libarchive 3.5.1"),
+            Flavor::Bsdtar
+        );
+        assert_eq!(flavor("cpio", "cpio (GNU cpio) 2.13"), Flavor::GnuCpio);
+        assert_eq!(
+            flavor("cpio", "bsdcpio 3.5.3 - libarchive 3.5.3"),
+            Flavor::Bsdcpio
+        );
+        assert_eq!(flavor("7za", SEVEN_ZIP), Flavor::SevenZipStandalone);
+        assert_eq!(flavor("unrar", "unrar-free 0.1.3"), Flavor::UnrarFree);
+        assert_eq!(flavor("unrar", "UNRAR 6.21 freeware"), Flavor::Unrar);
+        assert_eq!(flavor("tar", "busybox tar"), Flavor::Other);
+    }
+
+    #[test]
+    fn lists_seven_zip_formats() {
+        let formats = listed_formats(SEVEN_ZIP, Flavor::SevenZip);
+        for format in ["7z", "rar", "rar5"] {
+            assert!(formats.iter().any(|listed| listed == format), "{format}");
+        }
+        // The codecs come after the formats
+        assert!(!formats.iter().any(|listed| listed == "bcj2"));
+        assert!(listed_formats(SEVEN_ZIP, Flavor::Unar).is_empty());
+    }
+
+    #[test]
+    fn decides_support_by_flavor_and_version() {
+        assert!(tool(Flavor::GnuTar, "1.34", &[]).supports(Feature::Zstd));
+        assert!(!tool(Flavor::GnuTar, "1.30", &[]).supports(Feature::Zstd));
+        assert!(tool(Flavor::Bsdtar, "3.3.3", &[]).supports(Feature::Zstd));
+        assert!(!tool(Flavor::Bsdtar, "3.3.2", &[]).supports(Feature::Zstd));
+        assert!(tool(Flavor::Unrar, "6.21", &[]).supports(Feature::Rar5));
+        assert!(!tool(Flavor::UnrarFree, "0.1.3", &[]).supports(Feature::Rar5));
+
+        let without_rar = tool(Flavor::SevenZip, "16.02", &["7z", "zip"]);
+        assert!(!without_rar.supports(Feature::Rar5));
+        assert!(!without_rar.opens("rar"));
+        assert!(tool(Flavor::SevenZip, "16.02", &[]).opens("rar"));
+        assert!(!tool(Flavor::SevenZipReduced, "16.02", &[]).opens("rar"));
+    }
+}
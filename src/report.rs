@@ -0,0 +1,65 @@
+//! A summary of what an extraction wrote. It is worked out from the output
+//! directory rather than from the tool, so it looks the same for every
+//! backend.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::error::Result;
+
+/// What an extraction created or changed in the output directory.
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    /// Top-level entries of the output directory that were created or
+    /// written into
+    pub entries: Vec<PathBuf>,
+    /// Every path that was created or changed, parents before their
+    /// children
+    pub paths: Vec<PathBuf>,
+    pub files: u64,
+    pub directories: u64,
+    pub symlinks: u64,
+    /// Total size of the regular files
+    pub bytes: u64,
+    /// Time spent extracting and putting the contents in place
+    pub elapsed: Duration,
+}
+
+impl Report {
+    /// Counts the created or changed `paths` in `root`.
+    pub(crate) fn collect(root: &Path, paths: Vec<PathBuf>, elapsed: Duration) -> Result<Report> {
+        let mut report = Report {
+            elapsed,
+            ..Report::default()
+        };
+
+        for path in &paths {
+            let metadata = fs::symlink_metadata(path)?;
+            let file_type = metadata.file_type();
+            if file_type.is_symlink() {
+                report.symlinks += 1;
+            } else if file_type.is_dir() {
+                report.directories += 1;
+            } else {
+                report.files += 1;
+                report.bytes += metadata.len();
+            }
+
+            let top = match path
+                .strip_prefix(root)
+                .ok()
+                .and_then(|relative| relative.iter().next())
+            {
+                Some(name) => root.join(name),
+                None => path.clone(),
+            };
+            if !report.entries.contains(&top) {
+                report.entries.push(top);
+            }
+        }
+        report.entries.sort();
+        report.paths = paths;
+        Ok(report)
+    }
+}
@@ -1,10 +1,97 @@
-use std::fs;
-use std::io::{self, Read, Write};
-use std::path::Path;
-use std::process::{Command, Stdio};
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
 
-use anyhow::{anyhow, Result};
+use crate::error::{Error, Result};
 use crossbeam_channel::{select, tick, unbounded};
+use lazy_static::lazy_static;
+use std::sync::atomic::{AtomicI32, AtomicU64, Ordering};
+use std::sync::RwLock;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+lazy_static! {
+    static ref TRUSTED_DIRS: RwLock<Vec<PathBuf>> = RwLock::new(Vec::new());
+}
+
+/// Tools are stopped after running this many milliseconds, zero for never.
+static TIMEOUT_MILLIS: AtomicU64 = AtomicU64::new(0);
+
+/// The signal that cancelled extracting, zero as long as none did.
+static CANCELLED: AtomicI32 = AtomicI32::new(0);
+
+/// How long a tool that is being stopped gets to exit before it is killed.
+const KILL_GRACE: Duration = Duration::from_secs(2);
+
+/// Stops tools that run longer than `timeout` with [`Error::TimedOut`],
+/// `None` lets them take as long as they need.
+pub fn set_timeout(timeout: Option<Duration>) {
+    let millis = timeout.map_or(0, |timeout| (timeout.as_millis() as u64).max(1));
+    TIMEOUT_MILLIS.store(millis, Ordering::Relaxed);
+}
+
+fn timeout() -> Option<Duration> {
+    match TIMEOUT_MILLIS.load(Ordering::Relaxed) {
+        0 => None,
+        millis => Some(Duration::from_millis(millis)),
+    }
+}
+
+/// Turns SIGINT, SIGTERM and SIGHUP into a cancellation: running tools are
+/// stopped and [`Error::Cancelled`] is returned instead of the process
+/// ending on the spot, which leaves time to clean up partial output. A
+/// second signal ends the process right away.
+#[cfg(unix)]
+pub fn cancel_on_signals() {
+    extern "C" fn cancel(signal: libc::c_int) {
+        if CANCELLED.swap(signal, Ordering::SeqCst) != 0 {
+            unsafe {
+                libc::signal(signal, libc::SIG_DFL);
+                libc::raise(signal);
+            }
+        }
+    }
+
+    for signal in [libc::SIGINT, libc::SIGTERM, libc::SIGHUP] {
+        unsafe {
+            let mut action = std::mem::zeroed::<libc::sigaction>();
+            action.sa_sigaction = cancel as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            libc::sigemptyset(&mut action.sa_mask);
+            // Without SA_RESTART, blocking reads return and notice
+            action.sa_flags = 0;
+            libc::sigaction(signal, &action, std::ptr::null_mut());
+        }
+    }
+}
+
+/// Signals keep ending the process right away.
+#[cfg(not(unix))]
+pub fn cancel_on_signals() {}
+
+/// Fails with [`Error::Cancelled`] once a signal asked to stop.
+pub fn check_cancelled() -> Result<()> {
+    match CANCELLED.load(Ordering::SeqCst) {
+        0 => Ok(()),
+        signal => Err(Error::Cancelled { signal }),
+    }
+}
+
+/// Trusts tools in `dirs` on top of the system directories, such as a
+/// `~/.local/bin` the user manages. Directories that do not exist are
+/// ignored.
+pub fn add_trusted_dirs(dirs: &[PathBuf]) {
+    let mut trusted = TRUSTED_DIRS.write().unwrap();
+    for dir in dirs {
+        if let Ok(dir) = fs::canonicalize(dir) {
+            if !trusted.contains(&dir) {
+                trusted.push(dir);
+            }
+        }
+    }
+}
 
 pub fn is_trusted_bin_path(path: &Path) -> bool {
     let canonical_path = match fs::canonicalize(path) {
@@ -12,6 +99,15 @@ pub fn is_trusted_bin_path(path: &Path) -> bool {
         Err(_) => return false,
     };
 
+    if TRUSTED_DIRS
+        .read()
+        .unwrap()
+        .iter()
+        .any(|dir| canonical_path.starts_with(dir))
+    {
+        return true;
+    }
+
     #[cfg(target_os = "linux")]
     {
         let valid_prefixes = [
@@ -76,92 +172,809 @@ pub fn is_trusted_bin_path(path: &Path) -> bool {
     }
 }
 
-pub fn run_command(mut cmd: Command, verbose: bool) -> Result<()> {
-    cmd.stdin(Stdio::piped());
-    cmd.stdout(if verbose {
-        Stdio::piped()
-    } else {
-        Stdio::null()
+/// Looks up `name` on `PATH` and returns it only if it lives in a trusted
+/// system directory.
+pub fn find_trusted_binary(name: &str) -> Option<PathBuf> {
+    which::which(name)
+        .ok()
+        .filter(|path| is_trusted_bin_path(path))
+}
+
+/// Only the tail of a tool's error output is kept, that is where the
+/// reason for a failure ends up.
+const MAX_CAPTURED_STDERR: usize = 64 * 1024;
+
+/// A decoder that runs inside extr instead of as a separate tool. It wraps
+/// the data coming from the previous stage of a pipeline.
+pub type NativeDecoder = fn(Box<dyn Read + Send>) -> Box<dyn Read + Send>;
+
+#[derive(Debug)]
+enum Stage {
+    Command(Command),
+    Native(&'static str, NativeDecoder),
+}
+
+/// Commands run with the output of each one connected to the input of the
+/// next, like `rpm2cpio package.rpm | cpio -idm` in a shell. Native decoders
+/// can sit between the commands, but the last stage is always a command.
+#[derive(Debug)]
+pub struct Pipeline {
+    stages: Vec<Stage>,
+    input: Option<(PathBuf, u64)>,
+    feed: Option<String>,
+    secrets: Vec<String>,
+}
+
+impl Pipeline {
+    pub fn new(cmd: Command) -> Self {
+        Self {
+            stages: vec![Stage::Command(cmd)],
+            input: None,
+            feed: None,
+            secrets: Vec::new(),
+        }
+    }
+
+    /// A pipeline starting with the native decoder `decode`, named `name`
+    /// in errors.
+    pub fn native(name: &'static str, decode: NativeDecoder) -> Self {
+        Self {
+            stages: vec![Stage::Native(name, decode)],
+            input: None,
+            feed: None,
+            secrets: Vec::new(),
+        }
+    }
+
+    /// Feeds `file` from `offset` onwards into the first stage instead of
+    /// stdin, like `< file` in a shell.
+    pub fn input(mut self, file: &Path, offset: u64) -> Self {
+        self.input = Some((file.to_path_buf(), offset));
+        self
+    }
+
+    /// Feeds the output of the pipeline so far into `cmd`.
+    pub fn pipe(mut self, cmd: Command) -> Self {
+        self.stages.push(Stage::Command(cmd));
+        self
+    }
+
+    /// Feeds the output of the pipeline so far through the native decoder
+    /// `decode`.
+    pub fn pipe_native(mut self, name: &'static str, decode: NativeDecoder) -> Self {
+        self.stages.push(Stage::Native(name, decode));
+        self
+    }
+
+    /// Puts `args` in front of the arguments of the last command, where
+    /// tools expect their options.
+    pub fn prepend_args(mut self, args: &[&str]) -> Self {
+        if let Some(Stage::Command(cmd)) = self.stages.last_mut() {
+            let mut amended = Command::new(cmd.get_program());
+            amended.args(args).args(cmd.get_args());
+            if let Some(dir) = cmd.get_current_dir() {
+                amended.current_dir(dir);
+            }
+            for (key, value) in cmd.get_envs() {
+                match value {
+                    Some(value) => amended.env(key, value),
+                    None => amended.env_remove(key),
+                };
+            }
+            *cmd = amended;
+        }
+        self
+    }
+
+    /// Writes `line` to the first command as all of its input instead of
+    /// letting it read the terminal, for tools that take a password on
+    /// stdin. Pipelines reading a file or decoding natively first are not
+    /// fed.
+    pub fn feed_line(mut self, line: impl Into<String>) -> Self {
+        self.feed = Some(line.into());
+        self
+    }
+
+    /// Shows `secret` as `***` wherever it appears when the pipeline is
+    /// displayed, for passwords in arguments.
+    pub fn redact(mut self, secret: impl Into<String>) -> Self {
+        let secret = secret.into();
+        if !secret.is_empty() {
+            self.secrets.push(secret);
+        }
+        self
+    }
+
+    /// The external commands of the pipeline, in order.
+    pub fn commands(&self) -> impl Iterator<Item = &Command> {
+        self.stages.iter().filter_map(|stage| match stage {
+            Stage::Command(cmd) => Some(cmd),
+            Stage::Native(..) => None,
+        })
+    }
+
+    /// The file and offset read by the first stage, if any.
+    pub fn input_file(&self) -> Option<(&Path, u64)> {
+        self.input
+            .as_ref()
+            .map(|(file, offset)| (file.as_path(), *offset))
+    }
+}
+
+impl From<Command> for Pipeline {
+    fn from(cmd: Command) -> Self {
+        Pipeline::new(cmd)
+    }
+}
+
+/// The pipeline as a shell command line, quoted so it can be pasted into a
+/// shell. Native decoders show up by name in brackets, such as `[base64]`,
+/// and commands run in another directory are wrapped in `(cd DIR && ...)`.
+impl fmt::Display for Pipeline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.input {
+            Some((file, 0)) => write!(f, "< {} ", quote(file.as_os_str()))?,
+            Some((file, offset)) => {
+                write!(f, "tail -c +{} {} | ", offset + 1, quote(file.as_os_str()))?
+            }
+            None => {}
+        }
+        if let Some(line) = self.feed.as_ref().filter(|_| self.input.is_none()) {
+            write!(f, "echo {} | ", redact(line, &self.secrets))?;
+        }
+
+        for (index, stage) in self.stages.iter().enumerate() {
+            if index > 0 {
+                f.write_str(" | ")?;
+            }
+            match stage {
+                Stage::Native(name, _) => write!(f, "[{name}]")?,
+                Stage::Command(cmd) => write!(f, "{}", shell_command(cmd, &self.secrets))?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn quote(word: &OsStr) -> String {
+    shell_words::quote(&word.to_string_lossy()).into_owned()
+}
+
+/// `word` quoted for a shell, with every secret in it replaced by `***`.
+fn redact(word: &str, secrets: &[String]) -> String {
+    let word = secrets.iter().fold(word.to_string(), |word, secret| {
+        word.replace(secret.as_str(), "***")
     });
-    cmd.stderr(Stdio::piped());
+    shell_words::quote(&word).into_owned()
+}
+
+fn shell_command(cmd: &Command, secrets: &[String]) -> String {
+    let quote = |word: &OsStr| redact(&word.to_string_lossy(), secrets);
+
+    // Removing variables needs env, which takes its options before the
+    // assignments
+    let mut words = cmd
+        .get_envs()
+        .filter(|(_, value)| value.is_none())
+        .map(|(key, _)| format!("-u {}", quote(key)))
+        .collect::<Vec<_>>();
+    if !words.is_empty() {
+        words.insert(0, "env".to_string());
+    }
+    for (key, value) in cmd.get_envs() {
+        if let Some(value) = value {
+            words.push(format!("{}={}", key.to_string_lossy(), quote(value)));
+        }
+    }
+    words.push(quote(cmd.get_program()));
+    words.extend(cmd.get_args().map(quote));
+
+    let line = words.join(" ");
+    match cmd.get_current_dir() {
+        Some(dir) => format!("(cd {} && {line})", quote(dir.as_os_str())),
+        None => line,
+    }
+}
+
+/// Runs `cmd` on the terminal's stdin with its error output captured. Output is
+/// only passed through to the terminal in verbose mode.
+pub fn run_command(cmd: Command, verbose: bool) -> Result<()> {
+    run_pipeline(cmd.into(), verbose)
+}
+
+/// Runs every command in `pipeline`, all of which must be trusted binaries.
+/// The first command reads from the terminal, which the tools are handed
+/// when they ask for a password, and the output of the last one is only
+/// passed through to the terminal in verbose mode.
+pub fn run_pipeline(pipeline: Pipeline, verbose: bool) -> Result<()> {
+    run(pipeline, Output::Terminal, verbose).map(drop)
+}
+
+/// Like [`run_pipeline`], but with the output of the last command written
+/// to `output`.
+pub fn run_pipeline_to_file(pipeline: Pipeline, output: &Path, verbose: bool) -> Result<()> {
+    run(pipeline, Output::File(File::create(output)?), verbose).map(drop)
+}
+
+/// Runs `pipeline` without any input and returns the output of the last
+/// command, for reading listings and metadata.
+pub fn capture_pipeline(pipeline: Pipeline) -> Result<Vec<u8>> {
+    run(pipeline, Output::Capture, false)
+}
+
+/// Runs the trusted `cmd` without any input and returns everything it
+/// printed, whether it succeeded or not. Meant for reading version banners,
+/// which some tools only print along with a usage error.
+pub fn probe_command(mut cmd: Command) -> Result<String> {
+    let program = Path::new(cmd.get_program());
+    if !is_trusted_bin_path(program) {
+        return Err(Error::UntrustedTool {
+            tool: tool_name(&cmd),
+            path: program.to_path_buf(),
+        });
+    }
+
+    let output = cmd.stdin(Stdio::null()).output()?;
+    let mut printed = String::from_utf8_lossy(&output.stdout).into_owned();
+    printed.push_str(&String::from_utf8_lossy(&output.stderr));
+    Ok(printed)
+}
+
+enum Output {
+    Terminal,
+    File(File),
+    Capture,
+}
+
+fn tool_name(cmd: &Command) -> String {
+    Path::new(cmd.get_program())
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn run(pipeline: Pipeline, output: Output, verbose: bool) -> Result<Vec<u8>> {
+    for cmd in pipeline.commands() {
+        let program = Path::new(cmd.get_program());
+        if !is_trusted_bin_path(program) {
+            return Err(Error::UntrustedTool {
+                tool: tool_name(cmd),
+                path: program.to_path_buf(),
+            });
+        }
+    }
 
-    let mut child = cmd.spawn()?;
+    check_cancelled()?;
+
+    let Pipeline {
+        stages,
+        input,
+        mut feed,
+        ..
+    } = pipeline;
+    if !matches!(stages.last(), Some(Stage::Command(_))) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "a pipeline must end with a command",
+        )
+        .into());
+    }
 
     let (sender, receiver) = unbounded();
-    let timer = tick(std::time::Duration::from_millis(100));
-
-    let stdout = child.stdout.take();
-    let stderr = child.stderr.take();
-    let stdin = child.stdin.take();
-
-    if let Some(mut stdout) = stdout {
-        let sender = sender.clone();
-        std::thread::spawn(move || {
-            let mut buf = [0u8; 1024];
-            loop {
-                match stdout.read(&mut buf) {
-                    Ok(0) => break,
-                    Ok(n) => {
-                        let _ = io::stdout().write_all(&buf[..n]);
-                        let _ = io::stdout().flush();
+    let timer = tick(Duration::from_millis(100));
+    let mut group = None;
+    let mut terminal = None;
+
+    let last = stages.len() - 1;
+    let capture = matches!(output, Output::Capture);
+    let mut output = Some(output);
+    let mut previous_stdout = None;
+    let mut input = match &input {
+        Some((file, offset)) => {
+            let mut file = File::open(file)?;
+            file.seek(SeekFrom::Start(*offset))?;
+            Some(file)
+        }
+        None => None,
+    };
+    let mut decoded: Option<(Vec<&'static str>, Box<dyn Read + Send>)> = None;
+    let mut decoders = Vec::new();
+    let mut stdout_reader = None;
+    let mut commands = Vec::with_capacity(stages.len());
+    let mut children: Vec<Child> = Vec::with_capacity(stages.len());
+    let mut stderr_readers = Vec::with_capacity(stages.len());
+    let mut feeders = Vec::new();
+
+    for (index, stage) in stages.into_iter().enumerate() {
+        let mut cmd = match stage {
+            Stage::Native(name, decode) => {
+                let (mut names, source) = match decoded.take() {
+                    Some(decoded) => decoded,
+                    None => {
+                        let source: Box<dyn Read + Send> =
+                            match (previous_stdout.take(), input.take()) {
+                                (Some(stdout), _) => Box::new(stdout),
+                                (None, Some(file)) => Box::new(file),
+                                (None, None) if capture => Box::new(io::empty()),
+                                (None, None) => Box::new(io::stdin()),
+                            };
+                        (Vec::new(), source)
                     }
-                    Err(_) => break,
-                }
+                };
+                names.push(name);
+                decoded = Some((names, decode(source)));
+                continue;
             }
-            let _ = sender.send(());
+            Stage::Command(cmd) => cmd,
+        };
+
+        cmd.stdin(match previous_stdout.take() {
+            Some(stdout) => Stdio::from(stdout),
+            None if decoded.is_some() => Stdio::piped(),
+            None if input.is_some() => Stdio::from(input.take().unwrap()),
+            None if capture => Stdio::null(),
+            None if feed.is_some() => Stdio::piped(),
+            None => Stdio::inherit(),
         });
-    }
+        cmd.stdout(match output.take().filter(|_| index == last) {
+            None => Stdio::piped(),
+            Some(Output::File(file)) => Stdio::from(file),
+            Some(Output::Capture) => Stdio::piped(),
+            Some(Output::Terminal) if verbose => Stdio::piped(),
+            Some(Output::Terminal) => Stdio::null(),
+        });
+        cmd.stderr(Stdio::piped());
+        // Everything the tools start stays in their group, so that stopping
+        // the group reaches all of it
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            cmd.process_group(group.map_or(0, |leader| leader as i32));
+        }
 
-    if let Some(mut stderr) = stderr {
-        let sender = sender.clone();
-        std::thread::spawn(move || {
-            let mut buf = [0u8; 1024];
-            loop {
-                match stderr.read(&mut buf) {
-                    Ok(0) => break,
-                    Ok(n) => {
-                        let _ = io::stderr().write_all(&buf[..n]);
-                        let _ = io::stderr().flush();
-                    }
-                    Err(_) => break,
+        let mut child = match cmd.spawn() {
+            Ok(child) => child,
+            Err(error) => {
+                for mut child in children {
+                    let _ = child.kill();
+                    let _ = child.wait();
                 }
+                return Err(error.into());
             }
-            let _ = sender.send(());
-        });
-    }
+        };
 
-    if let Some(mut stdin) = stdin {
-        std::thread::spawn(move || {
-            let mut buf = [0u8; 1024];
-            loop {
-                match io::stdin().read(&mut buf) {
-                    Ok(0) => break,
-                    Ok(n) => {
-                        let _ = stdin.write_all(&buf[..n]);
-                        let _ = stdin.flush();
+        if group.is_none() {
+            group = Some(child.id());
+        }
+        if index < last {
+            previous_stdout = child.stdout.take();
+        }
+
+        stdout_reader = child.stdout.take().map(|mut stdout| {
+            let sender = sender.clone();
+            std::thread::spawn(move || {
+                let mut captured = Vec::new();
+                let mut buf = [0u8; 1024];
+                loop {
+                    match stdout.read(&mut buf) {
+                        Ok(0) => break,
+                        Ok(n) if capture => captured.extend_from_slice(&buf[..n]),
+                        Ok(n) => {
+                            let _ = io::stdout().write_all(&buf[..n]);
+                            let _ = io::stdout().flush();
+                        }
+                        Err(_) => break,
                     }
-                    Err(_) => break,
                 }
-            }
+                let _ = sender.send(());
+                captured
+            })
         });
+
+        stderr_readers.push(child.stderr.take().map(|mut stderr| {
+            let sender = sender.clone();
+            std::thread::spawn(move || {
+                let mut captured = Vec::new();
+                let mut buf = [0u8; 1024];
+                loop {
+                    match stderr.read(&mut buf) {
+                        Ok(0) => break,
+                        Ok(n) => {
+                            if verbose {
+                                let _ = io::stderr().write_all(&buf[..n]);
+                                let _ = io::stderr().flush();
+                            }
+                            captured.extend_from_slice(&buf[..n]);
+                            if captured.len() > MAX_CAPTURED_STDERR {
+                                captured.drain(..captured.len() - MAX_CAPTURED_STDERR);
+                            }
+                        }
+                        Err(_) => break,
+                    }
+                }
+                let _ = sender.send(());
+                captured
+            })
+        }));
+
+        if let Some((names, mut source)) = decoded.take() {
+            // A command that stops reading early is not the decoder's fault
+            let mut stdin = child.stdin.take().unwrap();
+            let sender = sender.clone();
+            decoders.push((
+                names.join(" | "),
+                std::thread::spawn(move || {
+                    let result = match io::copy(&mut source, &mut stdin) {
+                        Err(error) if error.kind() == io::ErrorKind::BrokenPipe => Ok(0),
+                        result => result,
+                    };
+                    drop(stdin);
+                    let _ = sender.send(());
+                    result
+                }),
+            ));
+        } else if let (Some(mut stdin), Some(line)) = (child.stdin.take(), feed.take()) {
+            feeders.push(std::thread::spawn(move || {
+                let _ = writeln!(stdin, "{line}");
+            }));
+        }
+
+        children.push(child);
+        commands.push(cmd);
     }
 
-    let status = loop {
+    let started = Instant::now();
+    let timeout = timeout();
+    let mut stopped = None;
+    let mut kill_at = None;
+    let mut statuses = vec![None; children.len()];
+    while statuses.iter().any(Option::is_none) {
         select! {
             recv(receiver) -> _ => {},
             recv(timer) -> _ => {
-                if let Some(status) = child.try_wait()? {
-                    break status;
+                for (child, status) in children.iter_mut().zip(statuses.iter_mut()) {
+                    if status.is_none() {
+                        *status = match poll(child)? {
+                            Poll::Running => None,
+                            Poll::Exited(status) => Some(status),
+                            Poll::WantsTerminal => {
+                                if terminal.is_none() {
+                                    terminal = hand_terminal(group);
+                                }
+                                None
+                            }
+                        };
+                    }
+                }
+
+                if stopped.is_none() {
+                    stopped = check_cancelled().err().or_else(|| {
+                        let timeout = timeout.filter(|timeout| started.elapsed() >= *timeout)?;
+                        let running = statuses.iter().position(Option::is_none)?;
+                        Some(Error::TimedOut {
+                            tool: tool_name(&commands[running]),
+                            timeout,
+                        })
+                    });
+                    if stopped.is_some() {
+                        stop_tools(&mut children, &statuses, group, false);
+                        kill_at = Some(Instant::now() + KILL_GRACE);
+                    }
+                } else if kill_at.is_some_and(|kill_at| Instant::now() >= kill_at) {
+                    stop_tools(&mut children, &statuses, group, true);
+                    kill_at = None;
                 }
             }
         }
+    }
+
+    if terminal.take().is_some() && stopped.is_none() {
+        // Ctrl-C went to the tools while they had the terminal
+        stopped = interrupted(&statuses).map(|signal| {
+            CANCELLED.store(signal, Ordering::SeqCst);
+            Error::Cancelled { signal }
+        });
+    }
+
+    // Whatever the tools left running in the background can keep their
+    // output open, it is killed when the output does not end in time
+    let finished = || {
+        stderr_readers.iter().flatten().all(JoinHandle::is_finished)
+            && stdout_reader.as_ref().map_or(true, JoinHandle::is_finished)
+            && decoders.iter().all(|(_, decoder)| decoder.is_finished())
+            && feeders.iter().all(JoinHandle::is_finished)
     };
+    if !wait_until(finished, KILL_GRACE) {
+        stop_tools(&mut children, &statuses, group, true);
+        wait_until(finished, KILL_GRACE);
+    }
+
+    let stderr = stderr_readers
+        .into_iter()
+        .flatten()
+        .filter_map(joined)
+        .map(|captured| String::from_utf8_lossy(&captured).into_owned())
+        .collect::<String>();
 
-    if !status.success() {
-        return Err(anyhow!(
-            "💥 Whoops! Command failed with exit status {status}"
-        ));
+    let stdout = stdout_reader.and_then(joined).unwrap_or_default();
+
+    feeders.into_iter().for_each(|feeder| {
+        joined(feeder);
+    });
+    let decoders = decoders
+        .into_iter()
+        .map(|(name, decoder)| (name, joined(decoder)))
+        .collect::<Vec<_>>();
+    if let Some(error) = stopped {
+        return Err(error);
     }
 
-    Ok(())
+    // Bad input to a native decoder is the cause of whatever the commands
+    // after it complain about
+    for (name, decoder) in decoders {
+        if let Some(Err(error)) = decoder {
+            return Err(Error::BackendFailed {
+                tool: name,
+                code: Some(1),
+                stderr: format!("{error}\n"),
+            });
+        }
+    }
+
+    // An earlier command killed by a closed pipe is a symptom, the command
+    // that exited with an error code is the cause.
+    let failed = commands
+        .iter()
+        .zip(statuses.into_iter().flatten())
+        .filter(|(_, status)| !status.success())
+        .min_by_key(|(_, status)| status.code().is_none());
+
+    if let Some((cmd, status)) = failed {
+        return Err(Error::BackendFailed {
+            tool: tool_name(cmd),
+            code: status.code(),
+            stderr,
+        });
+    }
+
+    Ok(stdout)
+}
+
+/// What became of a running tool.
+enum Poll {
+    Running,
+    Exited(ExitStatus),
+    /// Stopped for reading from or setting up the terminal, which only the
+    /// foreground process group may do
+    WantsTerminal,
+}
+
+#[cfg(unix)]
+fn poll(child: &mut Child) -> io::Result<Poll> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let mut status = 0;
+    let pid = unsafe {
+        libc::waitpid(
+            child.id() as libc::pid_t,
+            &mut status,
+            libc::WNOHANG | libc::WUNTRACED,
+        )
+    };
+    if pid < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if pid == 0 {
+        return Ok(Poll::Running);
+    }
+    if libc::WIFSTOPPED(status) {
+        return Ok(match libc::WSTOPSIG(status) {
+            libc::SIGTTIN | libc::SIGTTOU => Poll::WantsTerminal,
+            _ => Poll::Running,
+        });
+    }
+    Ok(Poll::Exited(ExitStatus::from_raw(status)))
+}
+
+#[cfg(not(unix))]
+fn poll(child: &mut Child) -> io::Result<Poll> {
+    Ok(child.try_wait()?.map_or(Poll::Running, Poll::Exited))
+}
+
+/// The terminal while it belongs to the tools, given back to extr when
+/// dropped.
+#[cfg(unix)]
+struct Terminal {
+    ttou: libc::sighandler_t,
+}
+
+#[cfg(unix)]
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        unsafe {
+            libc::tcsetpgrp(libc::STDIN_FILENO, libc::getpgrp());
+            libc::signal(libc::SIGTTOU, self.ttou);
+        }
+    }
+}
+
+/// Makes the tools the foreground process group of the terminal extr runs
+/// in, so that they can ask for a password, and lets them continue. Only
+/// possible while extr is in the foreground itself.
+#[cfg(unix)]
+fn hand_terminal(group: Option<u32>) -> Option<Terminal> {
+    let group = group? as libc::pid_t;
+    unsafe {
+        if libc::tcgetpgrp(libc::STDIN_FILENO) != libc::getpgrp() {
+            return None;
+        }
+        // In the background, extr must not be stopped for using the
+        // terminal itself
+        let ttou = libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+        if libc::tcsetpgrp(libc::STDIN_FILENO, group) != 0 {
+            libc::signal(libc::SIGTTOU, ttou);
+            return None;
+        }
+        libc::kill(-group, libc::SIGCONT);
+        Some(Terminal { ttou })
+    }
+}
+
+#[cfg(not(unix))]
+struct Terminal;
+
+#[cfg(not(unix))]
+fn hand_terminal(_group: Option<u32>) -> Option<Terminal> {
+    None
+}
+
+/// The signal from the terminal that ended one of the tools.
+#[cfg(unix)]
+fn interrupted(statuses: &[Option<ExitStatus>]) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+
+    statuses
+        .iter()
+        .flatten()
+        .filter_map(ExitStatusExt::signal)
+        .find(|signal| matches!(*signal, libc::SIGINT | libc::SIGQUIT))
+}
+
+#[cfg(not(unix))]
+fn interrupted(_statuses: &[Option<ExitStatus>]) -> Option<i32> {
+    None
+}
+
+/// Waits up to `limit` for `done`, and tells whether it got there.
+fn wait_until(done: impl Fn() -> bool, limit: Duration) -> bool {
+    let deadline = Instant::now() + limit;
+    while !done() {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    true
+}
+
+/// The result of `thread` when it is done, without waiting for it.
+fn joined<T>(thread: JoinHandle<T>) -> Option<T> {
+    if thread.is_finished() {
+        thread.join().ok()
+    } else {
+        None
+    }
+}
+
+/// Asks the tools that are still running, and everything they started, to
+/// stop, or kills them with `kill` set.
+#[cfg(unix)]
+fn stop_tools(
+    _children: &mut [Child],
+    _statuses: &[Option<ExitStatus>],
+    group: Option<u32>,
+    kill: bool,
+) {
+    let signal = if kill { libc::SIGKILL } else { libc::SIGTERM };
+    if let Some(group) = group {
+        unsafe {
+            libc::kill(-(group as libc::pid_t), signal);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn stop_tools(
+    children: &mut [Child],
+    statuses: &[Option<ExitStatus>],
+    _group: Option<u32>,
+    _kill: bool,
+) {
+    for (child, status) in children.iter_mut().zip(statuses) {
+        if status.is_none() {
+            let _ = child.kill();
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    // The timeout and cancellation are process wide
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    /// A shell that leaves a sleeping child behind, whose pid it writes to
+    /// `pid_file`.
+    fn lingering_shell(pid_file: &Path) -> Pipeline {
+        let mut cmd = Command::new("/bin/sh");
+        cmd.arg("-c")
+            .arg(r#"sleep 30 & echo $! > "$0"; sleep 30"#)
+            .arg(pid_file);
+        Pipeline::new(cmd)
+    }
+
+    /// Whether the process written to `pid_file` is gone, giving it a
+    /// moment to be reaped.
+    fn gone(pid_file: &Path) -> bool {
+        let pid: libc::pid_t = fs::read_to_string(pid_file)
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap();
+        wait_until(
+            || unsafe { libc::kill(pid, 0) != 0 } || is_zombie(pid),
+            Duration::from_secs(1),
+        )
+    }
+
+    fn is_zombie(pid: libc::pid_t) -> bool {
+        fs::read_to_string(format!("/proc/{pid}/stat")).is_ok_and(|stat| stat.contains(") Z "))
+    }
+
+    #[test]
+    fn stops_everything_the_tools_started_on_timeout() {
+        let _serial = SERIAL.lock().unwrap();
+        let dir = crate::scratch_dir("timeout");
+        let pid_file = dir.join("pid");
+
+        set_timeout(Some(Duration::from_millis(300)));
+        let started = Instant::now();
+        let result = run_pipeline(lingering_shell(&pid_file), false);
+        set_timeout(None);
+
+        assert!(matches!(result, Err(Error::TimedOut { .. })), "{result:?}");
+        assert!(started.elapsed() < KILL_GRACE);
+        assert!(gone(&pid_file));
+    }
+
+    #[test]
+    fn stops_everything_the_tools_started_on_cancel() {
+        let _serial = SERIAL.lock().unwrap();
+        let dir = crate::scratch_dir("cancel");
+        let pid_file = dir.join("pid");
+
+        let canceller = std::thread::spawn(|| {
+            std::thread::sleep(Duration::from_millis(300));
+            CANCELLED.store(libc::SIGTERM, Ordering::SeqCst);
+        });
+        let started = Instant::now();
+        let result = run_pipeline(lingering_shell(&pid_file), false);
+        canceller.join().unwrap();
+        CANCELLED.store(0, Ordering::SeqCst);
+
+        assert!(
+            matches!(
+                result,
+                Err(Error::Cancelled {
+                    signal: libc::SIGTERM
+                })
+            ),
+            "{result:?}"
+        );
+        assert!(started.elapsed() < KILL_GRACE);
+        assert!(gone(&pid_file));
+    }
 }
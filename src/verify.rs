@@ -0,0 +1,336 @@
+//! Making sure an archive is the one that was published before anything is
+//! extracted from it. Checksums can be given up front or are picked up from
+//! a `FILE.sha256` or `SHA256SUMS` file next to the archive, and detached
+//! signatures are checked with `gpg`, `minisign` or `ssh-keygen`.
+
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+
+use crate::error::{Error, Result};
+use crate::manifest::{sha256_file, Manifest};
+use crate::trust::{capture_pipeline, find_trusted_binary, run_pipeline, Pipeline};
+
+/// Files listing the checksums of all downloads in a directory.
+const CHECKSUM_LISTS: &[&str] = &["SHA256SUMS"];
+
+/// Extensions of detached signatures, in the order they are looked for.
+const SIGNATURE_EXTENSIONS: &[&str] = &["minisig", "asc", "sig"];
+
+/// Namespace `ssh-keygen -Y sign` is expected to have been run with.
+const SSH_NAMESPACE: &str = "file";
+
+/// The expected checksum of an archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Checksum {
+    /// Hex encoded SHA-256
+    Sha256(String),
+}
+
+impl FromStr for Checksum {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        let Some((algorithm, hex)) = value.split_once(':') else {
+            return Err(format!(
+                "invalid checksum {value}, expected the form sha256:HEX"
+            ));
+        };
+        if !algorithm.eq_ignore_ascii_case("sha256") {
+            return Err(format!(
+                "unsupported checksum algorithm {algorithm}, expected sha256"
+            ));
+        }
+        if hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("invalid SHA-256 {hex}, expected 64 hex digits"));
+        }
+        Ok(Checksum::Sha256(hex.to_lowercase()))
+    }
+}
+
+impl fmt::Display for Checksum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Checksum::Sha256(hex) => write!(f, "sha256:{hex}"),
+        }
+    }
+}
+
+/// What an archive is checked against before it is extracted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Verification {
+    /// Checksum the archive must have
+    pub checksum: Option<Checksum>,
+    /// Check the archive against a `FILE.sha256` or `SHA256SUMS` file next
+    /// to it, when one lists it
+    pub checksum_files: bool,
+    /// Require a valid detached signature of the archive, or of the
+    /// checksum file listing it
+    pub signature: bool,
+    /// Public key for the signature: a minisign public key, an OpenSSH
+    /// allowed signers file or a binary gpg keyring. gpg uses the keyring
+    /// of the user without one.
+    pub signing_key: Option<PathBuf>,
+}
+
+impl Default for Verification {
+    fn default() -> Self {
+        Self {
+            checksum: None,
+            checksum_files: true,
+            signature: false,
+            signing_key: None,
+        }
+    }
+}
+
+impl Verification {
+    /// Checks `file`, failing with [`Error::VerificationFailed`] when it does
+    /// not match its checksum or signature.
+    pub fn verify(&self, file: &Path, verbose: bool) -> Result<()> {
+        let mut expected = Vec::new();
+        if let Some(checksum) = &self.checksum {
+            expected.push((checksum.clone(), None));
+        }
+        if self.checksum_files {
+            for list in checksum_files(file) {
+                if let Some(checksum) = listed_checksum(&list, file)? {
+                    expected.push((checksum, Some(list)));
+                }
+            }
+        }
+
+        if !expected.is_empty() {
+            let actual = sha256_file(file)?;
+            let problems = expected
+                .iter()
+                .filter(|(Checksum::Sha256(hex), _)| *hex != actual)
+                .map(|(checksum, list)| match list {
+                    Some(list) => {
+                        format!("sha256 is {actual}, {} lists {checksum}", list.display())
+                    }
+                    None => format!("sha256 is {actual}, expected {checksum}"),
+                })
+                .collect::<Vec<_>>();
+            if !problems.is_empty() {
+                return Err(failed(file, problems));
+            }
+        }
+
+        if self.signature {
+            // A signed checksum file vouches for everything it lists
+            let signed = std::iter::once(file.to_path_buf())
+                .chain(expected.into_iter().filter_map(|(_, list)| list))
+                .find_map(|signed| signature_of(&signed).map(|signature| (signed, signature)));
+            let Some((signed, signature)) = signed else {
+                return Err(failed(
+                    file,
+                    vec![format!(
+                        "no detached signature found, looked for .{} files",
+                        SIGNATURE_EXTENSIONS.join(", .")
+                    )],
+                ));
+            };
+            self.verify_signature(&signed, &signature, verbose)
+                .map_err(|error| match error {
+                    Error::BackendFailed { tool, stderr, .. } => failed(
+                        file,
+                        vec![format!(
+                            "{tool} rejected {}: {}",
+                            signature.display(),
+                            stderr.trim().lines().last().unwrap_or("bad signature")
+                        )],
+                    ),
+                    error => error,
+                })?;
+        }
+        Ok(())
+    }
+
+    fn verify_signature(&self, file: &Path, signature: &Path, verbose: bool) -> Result<()> {
+        let mut header = Vec::new();
+        fs::File::open(signature)?
+            .take(64)
+            .read_to_end(&mut header)?;
+
+        if header.starts_with(b"untrusted comment:") {
+            let key = self.require_key(file, "a minisign public key")?;
+            let mut cmd = Command::new(signature_tool(file, "minisign")?);
+            cmd.arg("-V")
+                .arg("-p")
+                .arg(key)
+                .arg("-m")
+                .arg(file)
+                .arg("-x")
+                .arg(signature);
+            run_pipeline(cmd.into(), verbose)
+        } else if header.starts_with(b"-----BEGIN SSH SIGNATURE-----") {
+            let allowed_signers = self.require_key(file, "an allowed signers file")?;
+            let ssh_keygen = signature_tool(file, "ssh-keygen")?;
+
+            let mut find = Command::new(&ssh_keygen);
+            find.args(["-Y", "find-principals", "-s"])
+                .arg(signature)
+                .arg("-f")
+                .arg(allowed_signers);
+            let principals = capture_pipeline(find.into())?;
+            let principals = String::from_utf8_lossy(&principals);
+            let principal = principals.lines().next().unwrap_or_default();
+
+            let mut verify = Command::new(&ssh_keygen);
+            verify
+                .args(["-Y", "verify", "-n", SSH_NAMESPACE, "-I", principal, "-f"])
+                .arg(allowed_signers)
+                .arg("-s")
+                .arg(signature);
+            run_pipeline(Pipeline::new(verify).input(file, 0), verbose)
+        } else {
+            let mut cmd = match &self.signing_key {
+                Some(keyring) => {
+                    let mut cmd = Command::new(signature_tool(file, "gpgv")?);
+                    cmd.arg("--keyring").arg(keyring);
+                    cmd
+                }
+                None => {
+                    let mut cmd = Command::new(signature_tool(file, "gpg")?);
+                    cmd.args(["--batch", "--verify"]);
+                    cmd
+                }
+            };
+            cmd.arg(signature).arg(file);
+            run_pipeline(cmd.into(), verbose)
+        }
+    }
+
+    fn require_key(&self, file: &Path, what: &str) -> Result<&Path> {
+        self.signing_key.as_deref().ok_or_else(|| {
+            failed(
+                file,
+                vec![format!(
+                    "checking the signature needs {what}, see --signing-key"
+                )],
+            )
+        })
+    }
+}
+
+fn failed(file: &Path, problems: Vec<String>) -> Error {
+    Error::VerificationFailed {
+        file: file.to_path_buf(),
+        problems,
+    }
+}
+
+fn signature_tool(file: &Path, name: &str) -> Result<PathBuf> {
+    find_trusted_binary(name).ok_or_else(|| {
+        failed(
+            file,
+            vec![format!(
+                "{name} is needed to check the signature, but is not installed in a trusted directory"
+            )],
+        )
+    })
+}
+
+/// Checksum files next to `file` that may list it.
+fn checksum_files(file: &Path) -> Vec<PathBuf> {
+    let dir = file.parent().unwrap_or(Path::new(""));
+    let own = file.with_file_name(format!(
+        "{}.sha256",
+        file.file_name().unwrap_or_default().to_string_lossy()
+    ));
+
+    std::iter::once(own)
+        .chain(CHECKSUM_LISTS.iter().map(|name| dir.join(name)))
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+/// The checksum `list` gives for `file`. A `FILE.sha256` may hold nothing
+/// but the hash.
+fn listed_checksum(list: &Path, file: &Path) -> Result<Option<Checksum>> {
+    let name = file.file_name().unwrap_or_default().to_string_lossy();
+    let content = fs::read_to_string(list)?;
+    let bare = content.trim();
+    if !bare.is_empty() && !bare.contains(char::is_whitespace) {
+        return Ok(format!("sha256:{bare}").parse().ok());
+    }
+
+    Ok(Manifest::read(list)?
+        .entries
+        .into_iter()
+        .find(|entry| entry.path.trim_start_matches("./") == name)
+        .and_then(|entry| entry.sha256)
+        .and_then(|hex| format!("sha256:{hex}").parse().ok()))
+}
+
+/// The first detached signature of `file` that exists.
+fn signature_of(file: &Path) -> Option<PathBuf> {
+    let name = file.file_name()?.to_string_lossy().into_owned();
+    SIGNATURE_EXTENSIONS
+        .iter()
+        .map(|extension| file.with_file_name(format!("{name}.{extension}")))
+        .find(|signature| signature.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+    #[test]
+    fn parses_sha256_checksums() {
+        assert_eq!(
+            format!("SHA256:{}", HASH.to_uppercase()).parse(),
+            Ok(Checksum::Sha256(HASH.to_string()))
+        );
+        assert_eq!(
+            format!("sha256:{HASH}")
+                .parse::<Checksum>()
+                .unwrap()
+                .to_string(),
+            format!("sha256:{HASH}")
+        );
+    }
+
+    #[test]
+    fn refuses_malformed_checksums() {
+        for value in [
+            HASH.to_string(),
+            format!("md5:{HASH}"),
+            format!("sha256:{}", &HASH[1..]),
+            format!("sha256:{}g", &HASH[1..]),
+        ] {
+            assert!(value.parse::<Checksum>().is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn reads_bare_and_listed_checksums() {
+        let dir = crate::scratch_dir("verify-listed");
+        let file = dir.join("release.tar.gz");
+        let expected = Some(Checksum::Sha256(HASH.to_string()));
+
+        let own = dir.join("release.tar.gz.sha256");
+        fs::write(&own, format!("{HASH}\n")).unwrap();
+        assert_eq!(listed_checksum(&own, &file).unwrap(), expected);
+
+        let list = dir.join("SHA256SUMS");
+        fs::write(
+            &list,
+            format!("{}  other.zip\n{HASH} *./release.tar.gz\n", "0".repeat(64)),
+        )
+        .unwrap();
+        assert_eq!(listed_checksum(&list, &file).unwrap(), expected);
+        assert_eq!(
+            listed_checksum(&list, &dir.join("missing.zip")).unwrap(),
+            None
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
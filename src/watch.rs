@@ -0,0 +1,284 @@
+//! Picking up archives as they land in a directory. On Linux inotify tells
+//! when a file was written or moved in, elsewhere the directory is polled.
+//! Files are only handed out once they stopped growing.
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::dispose::volumes;
+use crate::error::Result;
+use crate::extractor::{get_format_extractor, identify, identify_contents};
+use crate::trust::check_cancelled;
+
+/// How long the size and modification time of a file have to stay the same
+/// before it counts as complete.
+const SETTLE_TIME: Duration = Duration::from_secs(2);
+
+/// How often the directory is looked at without inotify.
+#[cfg(not(target_os = "linux"))]
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+type Stamp = (u64, Option<SystemTime>);
+
+/// A directory archives are dropped into. Every archive is handed out once,
+/// and again when it is replaced.
+pub struct Inbox {
+    dir: PathBuf,
+    handed_out: HashMap<OsString, Stamp>,
+    #[cfg(target_os = "linux")]
+    events: inotify::Watch,
+}
+
+impl Inbox {
+    /// Starts watching `dir`. Archives already in it are returned by the
+    /// first call to [`Inbox::ready`].
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Inbox> {
+        let dir = dir.into();
+        fs::read_dir(&dir)?;
+
+        Ok(Inbox {
+            #[cfg(target_os = "linux")]
+            events: inotify::Watch::new(&dir)?,
+            dir,
+            handed_out: HashMap::new(),
+        })
+    }
+
+    /// Archives in the directory that were not handed out yet, once they
+    /// stopped growing. Only the first volume of a multi-volume set is
+    /// included.
+    pub fn ready(&mut self) -> Result<Vec<PathBuf>> {
+        let mut pending = self.candidates()?;
+        let mut ready = Vec::new();
+
+        while !pending.is_empty() {
+            thread::sleep(SETTLE_TIME);
+            check_cancelled()?;
+            let mut growing = Vec::new();
+            for (path, stamp) in pending {
+                match stamp_of(&path) {
+                    Ok(now) if now == stamp => {
+                        self.handed_out
+                            .insert(path.file_name().unwrap_or_default().to_owned(), stamp);
+                        ready.push(path);
+                    }
+                    Ok(now) => growing.push((path, now)),
+                    // Gone again, nothing to do
+                    Err(_) => {}
+                }
+            }
+            pending = growing;
+        }
+
+        let mut first_volumes = Vec::new();
+        for path in ready {
+            let first = volumes(&path)?
+                .into_iter()
+                .find(|volume| is_archive(volume));
+            if first.map_or(true, |first| first == path) {
+                first_volumes.push(path);
+            }
+        }
+        first_volumes.sort();
+        Ok(first_volumes)
+    }
+
+    /// Blocks until new archives are ready and returns them.
+    pub fn wait(&mut self) -> Result<Vec<PathBuf>> {
+        loop {
+            #[cfg(target_os = "linux")]
+            self.events.wait()?;
+            #[cfg(not(target_os = "linux"))]
+            thread::sleep(POLL_INTERVAL);
+            check_cancelled()?;
+
+            let ready = self.ready()?;
+            if !ready.is_empty() {
+                return Ok(ready);
+            }
+        }
+    }
+
+    /// Leaves `paths` out as they are now, such as what was just extracted
+    /// into the directory itself, so that archives among them are not
+    /// extracted again.
+    pub fn skip<'a>(&mut self, paths: impl IntoIterator<Item = &'a PathBuf>) {
+        let dir = fs::canonicalize(&self.dir).ok();
+        for path in paths {
+            let parent = path
+                .parent()
+                .and_then(|parent| fs::canonicalize(parent).ok());
+            if parent.is_none() || parent != dir {
+                continue;
+            }
+            if let (Some(name), Ok(stamp)) = (path.file_name(), stamp_of(path)) {
+                self.handed_out.insert(name.to_owned(), stamp);
+            }
+        }
+    }
+
+    fn candidates(&self) -> Result<Vec<(PathBuf, Stamp)>> {
+        let mut candidates = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            // Hidden files include the staging directories of extr itself
+            if name.to_string_lossy().starts_with('.') {
+                continue;
+            }
+
+            let path = entry.path();
+            let Ok(stamp) = stamp_of(&path) else {
+                continue;
+            };
+            if self.handed_out.get(&name) == Some(&stamp) || !is_archive(&path) {
+                continue;
+            }
+            candidates.push((path, stamp));
+        }
+        Ok(candidates)
+    }
+}
+
+/// Size and modification time of the regular file `path`.
+fn stamp_of(path: &Path) -> std::io::Result<Stamp> {
+    let metadata = fs::metadata(path)?;
+    if !metadata.is_file() {
+        return Err(std::io::ErrorKind::InvalidInput.into());
+    }
+    Ok((metadata.len(), metadata.modified().ok()))
+}
+
+fn is_archive(path: &Path) -> bool {
+    identify(path)
+        .and_then(|format| get_format_extractor(&format))
+        .is_some()
+        || identify_contents(path).is_some()
+}
+
+#[cfg(target_os = "linux")]
+mod inotify {
+    use std::ffi::CString;
+    use std::fs::File;
+    use std::io::{self, Read};
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::io::{AsRawFd, FromRawFd};
+    use std::path::Path;
+
+    /// Notifications for files closed after writing and files moved into a
+    /// directory.
+    pub struct Watch {
+        fd: File,
+    }
+
+    impl Watch {
+        pub fn new(dir: &Path) -> io::Result<Watch> {
+            let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let fd = unsafe { File::from_raw_fd(fd) };
+
+            let path = CString::new(dir.as_os_str().as_bytes())?;
+            let mask = libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO;
+            if unsafe { libc::inotify_add_watch(fd.as_raw_fd(), path.as_ptr(), mask) } < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Watch { fd })
+        }
+
+        /// Blocks until at least one event arrived or a signal interrupted
+        /// the wait. Which files the events are about does not matter, the
+        /// directory is looked at as a whole.
+        pub fn wait(&mut self) -> io::Result<()> {
+            let mut events = [0; 4096];
+            match self.fd.read(&mut events) {
+                Err(error) if error.kind() != io::ErrorKind::Interrupted => Err(error),
+                _ => Ok(()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(paths: &[PathBuf]) -> Vec<String> {
+        paths
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn hands_out_first_volumes_once() {
+        let dir = crate::scratch_dir("watch-inbox");
+        for name in [
+            "photos.zip",
+            "movie.part1.rar",
+            "movie.part2.rar",
+            "notes.txt",
+            ".photos.zip.extr-partial",
+        ] {
+            fs::write(dir.join(name), name).unwrap();
+        }
+
+        let mut inbox = Inbox::open(&dir).unwrap();
+        assert_eq!(
+            names(&inbox.ready().unwrap()),
+            ["movie.part1.rar", "photos.zip"]
+        );
+        assert!(inbox.ready().unwrap().is_empty());
+
+        // A replaced archive is handed out again
+        fs::write(dir.join("photos.zip"), "a newer upload").unwrap();
+        assert_eq!(names(&inbox.ready().unwrap()), ["photos.zip"]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn leaves_out_what_was_extracted_into_it() {
+        let dir = crate::scratch_dir("watch-skip");
+        let mut inbox = Inbox::open(&dir).unwrap();
+
+        let extracted = dir.join("nested.zip");
+        fs::write(&extracted, "from inside an archive").unwrap();
+        inbox.skip([&extracted]);
+        assert!(inbox.ready().unwrap().is_empty());
+
+        fs::write(&extracted, "uploaded over it").unwrap();
+        assert_eq!(names(&inbox.ready().unwrap()), ["nested.zip"]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn waits_until_files_stop_growing() {
+        let dir = crate::scratch_dir("watch-settle");
+        let upload = dir.join("upload.tar");
+        fs::write(&upload, "first half").unwrap();
+
+        let mut inbox = Inbox::open(&dir).unwrap();
+        let writer = {
+            let upload = upload.clone();
+            thread::spawn(move || {
+                thread::sleep(SETTLE_TIME / 2);
+                fs::write(upload, "first half, second half").unwrap();
+            })
+        };
+
+        assert_eq!(names(&inbox.ready().unwrap()), ["upload.tar"]);
+        // Only handed out after the second write
+        assert!(writer.is_finished());
+        writer.join().unwrap();
+        assert!(inbox.ready().unwrap().is_empty());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}